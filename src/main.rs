@@ -1,15 +1,22 @@
 #![warn(clippy::pedantic)]
 
 mod logger;
+mod report;
 mod web_server;
 mod websocket_handler;
 
-use clap::{clap_app, crate_name, crate_version};
-use failure::Error;
-use log::warn;
+use crate::websocket_handler::{
+    build_exclude, can_list_volumes, Directory, Roots, ScanSource, Snapshot, SnapshotFormat,
+    WalkOptions,
+};
+use clap::{clap_app, crate_name, crate_version, value_t, ArgMatches};
+use failure::{bail, Error};
+use log::{info, warn};
 use std::{
+    fs,
     net::{IpAddr, Ipv4Addr, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
 const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
@@ -27,8 +34,20 @@ async fn main() -> Result<(), Error> {
       (@arg debug: -v --verbose --debug ... "Show debug messages, multiple flags for higher verbosity")
       (@arg keep_open: -k "Keep program alive after websocket closed")
       (@arg no_browser: -n --("no-browser") "Don't open browser")
+      (@arg cache: -c --cache "Reuse the last scan of PATH with the same options, only reading directories that changed. Files written to in place don't change their directory, so they keep their cached size")
+      (@arg watch: -w --watch "Keep sizes current as files change after scanning")
+      (@arg one_file_system: -x --("one-file-system") "Don't scan directories on other filesystems")
+      (@arg follow_symlinks: -L --("follow-symlinks") "Count what symlinks point to instead of the links themselves")
+      (@arg exclude: -e --exclude +takes_value +multiple number_of_values(1) value_name("PATTERN") "Exclude files and directories matching a gitignore-style pattern")
+      (@arg exclude_from: --("exclude-from") +takes_value value_name("FILE") "Read exclude patterns from a gitignore-style file")
+      (@arg report: -r --report "Print a summary to stdout instead of starting the web server")
+      (@arg max_depth: -d --("max-depth") +takes_value value_name("N") default_value("1") "Directory depth shown by --report")
+      (@arg export: --export +takes_value value_name("FILE") "Scan without the web server and save the results as JSON")
+      (@arg export_format: --("export-format") +takes_value value_name("FORMAT") possible_values(&["json", "ncdu"]) default_value("json") "File format for --export")
+      (@arg compare: --compare +takes_value value_name("FILE") "Show how sizes changed since a scan saved with --export")
+      (@arg import: --import +takes_value value_name("FILE") "Browse a scan saved with --export or ncdu -o instead of scanning PATH")
 
-      (@arg path: [PATH] +required default_value(".") "Path")
+      (@arg path: [PATH]... "Paths to scan, more than one are shown under a shared top level. Without any the browser lists the mounted filesystems to pick one, or the current directory is scanned where they can't be listed")
   )
   .get_matches();
 
@@ -43,9 +62,138 @@ async fn main() -> Result<(), Error> {
 
     let no_browser = matches.is_present("no_browser");
     let keep_open = matches.is_present("keep_open");
-    let path: PathBuf = matches.value_of("path").unwrap().into();
+    let report = matches.is_present("report");
+    let export_path = matches.value_of("export").map(PathBuf::from);
+
+    let mut paths: Vec<PathBuf> = matches
+        .values_of("path")
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect();
+    // the current directory where there's no volume list to pick from
+    if paths.is_empty() && (report || export_path.is_some() || !can_list_volumes()) {
+        paths.push(PathBuf::from("."));
+    }
+
+    let walk_options = get_walk_options(&matches, &paths)?;
+
+    let imported = matches
+        .value_of("import")
+        .map(|import_path| Snapshot::read(Path::new(import_path)))
+        .transpose()?;
+    let previous = matches
+        .value_of("compare")
+        .map(|compare_path| Snapshot::read(Path::new(compare_path)))
+        .transpose()?;
+
+    if report || export_path.is_some() {
+        let max_depth = value_t!(matches, "max_depth", usize)?;
+
+        if imported.is_none() && paths.len() > 1 {
+            bail!("--report and --export only scan one PATH");
+        }
+
+        let mut snapshot = match imported {
+            Some(snapshot) => snapshot,
+            None => Snapshot {
+                tree: Directory::scan(paths[0].clone(), &walk_options),
+                root_path: paths[0].clone(),
+            },
+        };
 
-    web_server::start(SocketAddr::new(ip, port), path, keep_open, no_browser).await?;
+        if let Some(previous) = previous {
+            snapshot.tree.compare(&previous.tree);
+        }
+
+        if let Some(export_path) = export_path {
+            let format = value_t!(matches, "export_format", SnapshotFormat)?;
+            snapshot.write(&export_path, format)?;
+            info!("exported to {:?}", export_path);
+        }
+
+        if report {
+            report::print(&snapshot.root_path, &snapshot.tree, max_depth)?;
+        }
+
+        return Ok(());
+    }
+
+    let source = get_scan_source(&matches, paths, walk_options, imported, previous)?;
+
+    web_server::start(SocketAddr::new(ip, port), source, keep_open, no_browser).await?;
 
     Ok(())
 }
+
+// without PATH the browser picks a volume
+fn get_scan_source(
+    matches: &ArgMatches,
+    paths: Vec<PathBuf>,
+    walk_options: WalkOptions,
+    imported: Option<Snapshot>,
+    previous: Option<Snapshot>,
+) -> Result<ScanSource, Error> {
+    let source = match imported {
+        Some(mut snapshot) => {
+            if let Some(previous) = previous {
+                snapshot.tree.compare(&previous.tree);
+            }
+
+            ScanSource::Imported(Arc::new(snapshot))
+        }
+
+        None if paths.is_empty() => {
+            if previous.is_some() {
+                bail!("--compare needs a PATH");
+            }
+
+            ScanSource::Volumes {
+                walk_options,
+                watch: matches.is_present("watch"),
+                cache: matches.is_present("cache"),
+            }
+        }
+
+        // a saved scan is of one root, not the virtual top level
+        None if paths.len() > 1 && previous.is_some() => {
+            bail!("--compare only compares one PATH");
+        }
+
+        None => ScanSource::Live {
+            roots: Roots::new(paths),
+            walk_options,
+            previous: previous.map(Arc::new),
+            watch: matches.is_present("watch"),
+            cache: matches.is_present("cache"),
+        },
+    };
+
+    Ok(source)
+}
+
+fn get_walk_options(matches: &ArgMatches, paths: &[PathBuf]) -> Result<WalkOptions, Error> {
+    // patterns starting with / can only be relative to one root
+    let exclude_root = if paths.len() == 1 {
+        paths[0].as_path()
+    } else {
+        Path::new("")
+    };
+
+    let patterns: Vec<&str> = matches.values_of("exclude").into_iter().flatten().collect();
+    let exclude_file = matches.value_of("exclude_from").map(Path::new);
+    let exclude = build_exclude(exclude_root, patterns.iter().copied(), exclude_file)?;
+
+    // the file's too, it can change between runs
+    let mut exclude_patterns: Vec<String> = patterns.iter().map(ToString::to_string).collect();
+    if let Some(exclude_file) = exclude_file {
+        exclude_patterns.extend(fs::read_to_string(exclude_file)?.lines().map(String::from));
+    }
+
+    Ok(WalkOptions {
+        one_file_system: matches.is_present("one_file_system"),
+        exclude,
+        exclude_patterns,
+        follow_symlinks: matches.is_present("follow_symlinks"),
+    })
+}
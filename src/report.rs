@@ -0,0 +1,143 @@
+use crate::websocket_handler::{Directory, SkipReason};
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+const BAR_WIDTH: usize = 10;
+
+/// Print a du-style tree of directory sizes to stdout.
+pub fn print(root_path: &Path, tree: &Directory, max_depth: usize) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    writeln!(out, "--- {} ---", root_path.display())?;
+    write_children(&mut out, tree, 0, max_depth)?;
+    writeln!(out)?;
+    write!(out, "Total disk usage: {}", format_bytes(tree.total_size))?;
+    if let Some(previous_size) = tree.previous_size {
+        write!(out, " ({})", format_change(tree.total_size, previous_size))?;
+    }
+    writeln!(out)?;
+    if tree.unreadable_count > 0 {
+        writeln!(
+            out,
+            "{} files or directories couldn't be read, so this is too low",
+            tree.unreadable_count
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_children<W: Write>(
+    out: &mut W,
+    dir: &Directory,
+    depth: usize,
+    max_depth: usize,
+) -> io::Result<()> {
+    if depth >= max_depth {
+        return Ok(());
+    }
+
+    let mut children: Vec<_> = dir.entries().collect();
+    children.sort_by(|(left_name, left), (right_name, right)| {
+        right
+            .total_size
+            .cmp(&left.total_size)
+            .then_with(|| left_name.cmp(right_name))
+    });
+
+    for (name, child) in children {
+        let indent = "  ".repeat(depth);
+
+        let skipped_label = match child.skipped {
+            Some(SkipReason::MountPoint) => Some("mount"),
+            Some(SkipReason::Excluded) => Some("excluded"),
+            None => None,
+        };
+        if let Some(label) = skipped_label {
+            writeln!(
+                out,
+                "{:>10} [{:^width$}] {}/{}",
+                "",
+                label,
+                indent,
+                name,
+                width = BAR_WIDTH
+            )?;
+            continue;
+        }
+
+        let unreadable = if child.unreadable_count > 0 {
+            " (incomplete)"
+        } else {
+            ""
+        };
+
+        let change = child
+            .previous_size
+            .map(|previous_size| format!("{:>12} ", format_change(child.total_size, previous_size)))
+            .unwrap_or_default();
+
+        writeln!(
+            out,
+            "{:>10} {}[{}] {}/{}{}",
+            format_bytes(child.total_size),
+            change,
+            bar(child.total_size, dir.total_size),
+            indent,
+            name,
+            unreadable
+        )?;
+
+        write_children(out, child, depth + 1, max_depth)?;
+    }
+
+    Ok(())
+}
+
+fn bar(size: u64, parent_size: u64) -> String {
+    #[allow(clippy::cast_possible_truncation)]
+    let filled = if parent_size == 0 {
+        0
+    } else {
+        (u128::from(size) * BAR_WIDTH as u128 / u128::from(parent_size)) as usize
+    };
+
+    format!("{:<width$}", "#".repeat(filled), width = BAR_WIDTH)
+}
+
+fn format_change(size: u64, previous_size: u64) -> String {
+    if size >= previous_size {
+        format!("+{}", format_bytes(size - previous_size))
+    } else {
+        format!("-{}", format_bytes(previous_size - size))
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[test]
+fn test_format_bytes() {
+    assert_eq!(format_bytes(0), "0 B");
+    assert_eq!(format_bytes(1023), "1023 B");
+    assert_eq!(format_bytes(1024), "1.0 KiB");
+    assert_eq!(format_bytes(3_879_731), "3.7 MiB");
+}
@@ -1,24 +1,33 @@
-use crate::websocket_handler::WebsocketHandler;
+use crate::websocket_handler::{ScanService, ScanSource, WebsocketHandler};
 use failure::Error;
 use futures::{channel::mpsc, prelude::*};
 use log::{debug, info, warn};
-use std::{net::SocketAddr, path::PathBuf, time::Duration};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use warp::{path::FullPath, Filter};
 
 include!(concat!(env!("OUT_DIR"), "/parceljs.rs"));
 
 pub async fn start(
     mut addr: SocketAddr,
-    root_path: PathBuf,
+    source: ScanSource,
     keep_open: bool,
     no_browser: bool,
 ) -> Result<(), Error> {
     info!("starting http/websocket server");
 
+    // shared by every websocket
+    let service = ScanService::start(source);
+
     let mut tries: u8 = 0;
     loop {
-        let root_path = root_path.clone();
-        let ok = _start(addr, root_path, keep_open);
+        let ok = _start(addr, service.clone(), keep_open);
 
         match ok {
             Ok((addr, fut)) => {
@@ -55,27 +64,32 @@ pub async fn start(
 
 fn _start(
     addr: SocketAddr,
-    root_path: PathBuf,
+    service: ScanService,
     keep_open: bool,
 ) -> Result<(SocketAddr, impl Future<Output = ()> + 'static), warp::Error> {
     let (shutdown_sender, mut shutdown_receiver) = mpsc::channel(1);
+    let connections = Arc::new(AtomicUsize::new(0));
 
     let routes = warp::path("ws")
         .and(warp::ws())
         .map(move |ws: warp::ws::Ws| {
-            let root_path = root_path.clone();
+            let service = service.clone();
+            let connections = connections.clone();
             let mut shutdown_sender = shutdown_sender.clone();
 
             ws.on_upgrade(move |ws| async move {
                 debug!("websocket upgraded");
+                connections.fetch_add(1, Ordering::SeqCst);
 
                 {
-                    WebsocketHandler::run(&root_path, ws).await;
+                    WebsocketHandler::run(&service, ws).await;
                 }
 
                 info!("ws stopped");
 
-                if !keep_open {
+                // other tabs are still using the scan
+                let last = connections.fetch_sub(1, Ordering::SeqCst) == 1;
+                if last && !keep_open {
                     shutdown_sender.send(()).await.unwrap();
                 }
             })
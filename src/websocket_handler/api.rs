@@ -1,21 +1,215 @@
 use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, fs, io};
 
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type")]
 pub enum Entry {
+    #[serde(rename_all = "camelCase")]
     File {
         path: Vec<String>,
         size: u64,
+        allocated_size: u64,
+        /// only when comparing against an older scan
+        previous_size: Option<u64>,
+        size_change: Option<i64>,
+        /// seconds since the unix epoch
+        modified: Option<u64>,
+        accessed: Option<u64>,
     },
+    #[serde(rename_all = "camelCase")]
     Directory {
         path: Vec<String>,
         size: u64,
+        allocated_size: u64,
         updating: UpdatingStatus,
+        /// extra links to already counted files, not included in `size`
+        hard_links: u64,
+        hard_links_size: u64,
+        previous_size: Option<u64>,
+        size_change: Option<i64>,
+        /// recursive, for inode usage
+        file_count: u64,
+        dir_count: u64,
+        /// newest of any file inside
+        last_modified: Option<u64>,
+        last_accessed: Option<u64>,
+        age_histogram: AgeHistogram,
+        /// why it couldn't be listed
+        error: Option<String>,
+        /// recursive, when not 0 the totals are missing something
+        unreadable_count: u64,
+    },
+    /// directory on another filesystem that wasn't scanned
+    MountPoint { path: Vec<String> },
+    /// directory matching an exclude pattern that wasn't scanned
+    Excluded { path: Vec<String> },
+    /// file that couldn't be read, not counted anywhere
+    Error { path: Vec<String>, message: String },
+    /// link that wasn't followed, `size` is the link's own and isn't in any total
+    Symlink {
+        path: Vec<String>,
+        target: String,
+        size: u64,
+    },
+    /// neither file, directory nor symlink, takes no space
+    Special {
+        path: Vec<String>,
+        kind: SpecialKind,
     },
 }
 
-#[derive(Debug, Serialize, Clone, Copy)]
+impl Entry {
+    pub fn path(&self) -> &[String] {
+        match self {
+            Entry::File { path, .. }
+            | Entry::Directory { path, .. }
+            | Entry::MountPoint { path }
+            | Entry::Excluded { path }
+            | Entry::Error { path, .. }
+            | Entry::Symlink { path, .. }
+            | Entry::Special { path, .. } => path,
+        }
+    }
+}
+
+pub fn size_change(size: u64, previous_size: Option<u64>) -> Option<i64> {
+    let clamp = |difference: u64| i64::try_from(difference).unwrap_or(i64::MAX);
+
+    previous_size.map(|previous_size| {
+        if size >= previous_size {
+            clamp(size - previous_size)
+        } else {
+            -clamp(previous_size - size)
+        }
+    })
+}
+
+/// Total of all files with one extension.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionSize {
+    /// lowercase, empty for files without one
+    pub extension: String,
+    pub size: u64,
+    pub allocated_size: u64,
+    pub file_count: u64,
+}
+
+/// Total of all files owned by one user or group.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerSize {
+    /// uid or gid
+    pub id: u32,
+    /// None if this computer's user database doesn't have it
+    pub name: Option<String>,
+    pub size: u64,
+    pub allocated_size: u64,
+    pub file_count: u64,
+}
+
+/// Files with the same contents.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// of each file
+    pub size: u64,
+    pub paths: Vec<Vec<String>>,
+}
+
+/// A mounted filesystem that can be picked to scan.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Volume {
+    pub mount_point: String,
+    /// what's mounted, like a device or a network share
+    pub source: String,
+    pub fs_type: String,
+    pub total_space: u64,
+    /// including what's reserved for root
+    pub used_space: u64,
+    /// to normal users
+    pub available_space: u64,
+}
+
+impl DuplicateGroup {
+    /// freed by keeping only one of them
+    pub fn reclaimable_size(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// upper bounds of every bucket but the last, in seconds
+const AGE_BUCKETS: [u64; 4] = [DAY, 7 * DAY, 30 * DAY, 365 * DAY];
+
+/// Bytes last touched less than a day, a week, a month, a year, and longer ago,
+/// counted from when they were scanned. Files without times are left out.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct AgeHistogram(pub [u64; AGE_BUCKETS.len() + 1]);
+
+impl AgeHistogram {
+    pub fn add(&mut self, touched: u64, now: u64, size: u64) {
+        let age = now.saturating_sub(touched);
+        let bucket = AGE_BUCKETS
+            .iter()
+            .position(|&max_age| age < max_age)
+            .unwrap_or(AGE_BUCKETS.len());
+
+        self.0[bucket] += size;
+    }
+
+    pub fn subtract(&mut self, other: &Self) {
+        for (bucket, other_bucket) in self.0.iter_mut().zip(&other.0) {
+            *bucket = bucket.saturating_sub(*other_bucket);
+        }
+    }
+
+    pub fn add_all(&mut self, other: &Self) {
+        for (bucket, other_bucket) in self.0.iter_mut().zip(&other.0) {
+            *bucket += *other_bucket;
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SpecialKind {
+    Socket,
+    Fifo,
+    CharDevice,
+    BlockDevice,
+    /// anything else the platform has
+    Other,
+}
+
+impl From<fs::FileType> for SpecialKind {
+    #[cfg(unix)]
+    fn from(file_type: fs::FileType) -> Self {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_socket() {
+            Self::Socket
+        } else if file_type.is_fifo() {
+            Self::Fifo
+        } else if file_type.is_char_device() {
+            Self::CharDevice
+        } else if file_type.is_block_device() {
+            Self::BlockDevice
+        } else {
+            Self::Other
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(_file_type: fs::FileType) -> Self {
+        Self::Other
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub enum UpdatingStatus {
     Idle,
@@ -27,13 +221,24 @@ pub enum UpdatingStatus {
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type")]
 pub enum EventMessage {
+    /// sent when the websocket opens with a scan to show,
+    /// and again each time a volume is picked to scan
+    #[serde(rename_all = "camelCase")]
+    SessionInfo {
+        /// empty when several roots are under a virtual top level
+        root_path: Vec<String>,
+        /// delete and reveal aren't available
+        read_only: bool,
+    },
+
     #[serde(rename_all = "camelCase")]
     DirectoryChange {
         /// always Entry::Directory
         current_directory: Entry,
         entries: Vec<Entry>,
         breadcrumb_entries: Vec<Entry>,
-        available_space: u64,
+        /// None when browsing an imported scan
+        available_space: Option<u64>,
     },
 
     SizeUpdate {
@@ -41,10 +246,69 @@ pub enum EventMessage {
         entry: Entry,
     },
 
+    /// biggest files in the whole tree, largest first, resent while scanning
+    LargestFiles {
+        /// always Entry::File
+        entries: Vec<Entry>,
+    },
+
+    /// breakdown of everything under `path`, biggest first
+    Extensions {
+        path: Vec<String>,
+        extensions: Vec<ExtensionSize>,
+    },
+
+    /// who owns everything under `path`, biggest first
+    Owners {
+        path: Vec<String>,
+        users: Vec<OwnerSize>,
+        groups: Vec<OwnerSize>,
+    },
+
+    /// answer to `ControlMessage::Volumes`, also sent first when there's no scan yet
+    Volumes { volumes: Vec<Volume> },
+
+    /// answer to `ControlMessage::FindDuplicates`, biggest savings first
+    #[serde(rename_all = "camelCase")]
+    Duplicates {
+        groups: Vec<DuplicateGroup>,
+        reclaimable_size: u64,
+    },
+
     Deleting {
         path: Vec<String>,
         status: DeletingStatus,
     },
+
+    /// something the client asked for went wrong
+    Error {
+        /// what it was about, if anything
+        path: Option<Vec<String>>,
+        message: String,
+        kind: ErrorKind,
+    },
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    /// a control message that couldn't be parsed
+    InvalidMessage,
+    NotFound,
+    PermissionDenied,
+    /// not possible in this session, like deleting from an imported scan
+    Unsupported,
+    Other,
+}
+
+impl From<&io::Error> for ErrorKind {
+    fn from(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            _ => Self::Other,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Clone, Copy)]
@@ -58,7 +322,38 @@ pub enum DeletingStatus {
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type")]
 pub enum ControlMessage {
-    ChangeDirectory { path: Vec<String> },
-    Delete { path: Vec<String> },
-    Reveal { path: Vec<String> },
+    ChangeDirectory {
+        path: Vec<String>,
+    },
+    Delete {
+        path: Vec<String>,
+    },
+    Reveal {
+        path: Vec<String>,
+    },
+    /// subscribe to the largest files, 0 to stop
+    LargestFiles {
+        count: usize,
+    },
+    /// get extension breakdowns of the current directory
+    Extensions {
+        subscribe: bool,
+    },
+    /// get owner breakdowns of the current directory
+    Owners {
+        subscribe: bool,
+    },
+    /// hash same-size files once scanning is done
+    FindDuplicates,
+    /// walk this again to correct its totals after changes made elsewhere
+    Rescan {
+        path: Vec<String>,
+    },
+    /// list the mounted filesystems
+    Volumes,
+    /// switch to a scan of this mounted filesystem, started if nobody scanned it yet
+    #[serde(rename_all = "camelCase")]
+    ScanVolume {
+        mount_point: String,
+    },
 }
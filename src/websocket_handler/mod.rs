@@ -1,69 +1,483 @@
 mod api;
 mod worker;
 
+pub use self::worker::{
+    build_exclude, can_list_volumes, Directory, Roots, SkipReason, Snapshot, SnapshotFormat,
+    WalkOptions,
+};
 use self::{
-    api::{DeletingStatus, Entry, EventMessage, UpdatingStatus},
-    worker::{get_components, spawn_scanner_stream, ScannerControlMessage},
+    api::{DeletingStatus, Entry, ErrorKind, EventMessage, UpdatingStatus, Volume},
+    worker::{get_volumes, spawn_scanner, spawn_snapshot_stream, Scanner, ScannerControlMessage},
 };
 use crate::websocket_handler::api::ControlMessage;
 use failure::{Error, ResultExt};
 use futures::{
     channel::mpsc::{unbounded as unbounded_stream, UnboundedReceiver, UnboundedSender},
     future,
-    future::Either,
+    future::{Either, RemoteHandle},
     lock::Mutex,
     prelude::*,
 };
 use log::{debug, info, warn};
-use std::{collections::HashMap, fs, path::PathBuf, sync::Arc, thread, time::Duration};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{self, Component, Path, PathBuf},
+    sync::{Arc, Weak},
+    thread,
+    time::Duration,
+};
+
+/// Where a websocket session gets its tree from.
+#[derive(Debug, Clone)]
+pub enum ScanSource {
+    /// scan the filesystem at these paths
+    Live {
+        roots: Roots,
+        walk_options: WalkOptions,
+        /// older scan to compare against once scanning finishes
+        previous: Option<Arc<Snapshot>>,
+        /// keep the totals current once scanning finishes
+        watch: bool,
+        /// start from the last scan of each root and save this one for next time
+        cache: bool,
+    },
+    /// browse a scan saved with --export
+    Imported(Arc<Snapshot>),
+    /// nothing until a mounted filesystem is picked in the browser
+    Volumes {
+        walk_options: WalkOptions,
+        watch: bool,
+        cache: bool,
+    },
+}
+
+/// A tree websockets can be shown.
+#[derive(Debug, Clone)]
+enum Scan {
+    Live {
+        roots: Roots,
+        scanner: Scanner,
+    },
+    /// nothing changes, so each websocket browses it on its own
+    Imported(Arc<Snapshot>),
+}
+
+/// One scan for every websocket, so another tab or a reload doesn't scan again.
+#[derive(Debug, Clone)]
+pub struct ScanService {
+    /// shown when a websocket opens, None starts at the volumes
+    initial: Option<Scan>,
+    /// for volumes picked in the browser
+    walk_options: WalkOptions,
+    watch: bool,
+    cache: bool,
+    /// by mount point, everyone picking the same one shares its scan
+    /// and it stops once nobody shows it anymore
+    volumes: Arc<Mutex<HashMap<PathBuf, Weak<Scan>>>>,
+}
+
+impl ScanService {
+    /// Live scans start right away, before anything connects.
+    pub fn start(source: ScanSource) -> Self {
+        let (initial, walk_options, watch, cache) = match source {
+            ScanSource::Live {
+                roots,
+                walk_options,
+                previous,
+                watch,
+                cache,
+            } => {
+                let scanner =
+                    spawn_scanner(roots.clone(), walk_options.clone(), previous, watch, cache);
+
+                (
+                    Some(Scan::Live { roots, scanner }),
+                    walk_options,
+                    watch,
+                    cache,
+                )
+            }
+
+            ScanSource::Imported(snapshot) => (
+                Some(Scan::Imported(snapshot)),
+                WalkOptions::default(),
+                false,
+                false,
+            ),
+
+            ScanSource::Volumes {
+                walk_options,
+                watch,
+                cache,
+            } => (None, walk_options, watch, cache),
+        };
+
+        Self {
+            initial,
+            walk_options,
+            watch,
+            cache,
+            volumes: Arc::default(),
+        }
+    }
+
+    /// The scan of `mount_point`, started again if nobody was showing it.
+    async fn volume(&self, mount_point: PathBuf) -> Arc<Scan> {
+        let mut volumes = self.volumes.lock().await;
+        volumes.retain(|_, scan| scan.strong_count() > 0);
+
+        if let Some(scan) = volumes.get(&mount_point).and_then(Weak::upgrade) {
+            return scan;
+        }
+
+        // filesystems mounted inside are volumes of their own
+        let walk_options = WalkOptions {
+            one_file_system: true,
+            ..self.walk_options.clone()
+        };
+        let roots = Roots::new(vec![mount_point.clone()]);
+        let scanner = spawn_scanner(roots.clone(), walk_options, None, self.watch, self.cache);
+
+        let scan = Arc::new(Scan::Live { roots, scanner });
+        volumes.insert(mount_point, Arc::downgrade(&scan));
+        scan
+    }
+}
+
+/// Every mounted filesystem, on another thread since a slow one can hang.
+async fn list_volumes() -> Result<Vec<Volume>, Error> {
+    let volumes = tokio::task::spawn_blocking(get_volumes)
+        .await
+        .with_context(|_| "get_volumes panic?")??;
+
+    Ok(volumes)
+}
 
 pub struct WebsocketHandler {
-    root_path: Vec<String>,
+    service: ScanService,
+    /// nothing before a volume is picked
+    roots: Roots,
+    /// imported scans don't match this machine's files
+    read_only: bool,
     current_dir: Vec<String>,
-    thread_control_sender: UnboundedSender<ScannerControlMessage>,
+    /// None before a volume is picked
+    thread_control_sender: Option<UnboundedSender<ScannerControlMessage>>,
+    /// the picked volume's scan runs as long as some websocket has it
+    volume_scan: Option<Arc<Scan>>,
+    /// goes through the size update stream, in order with the scan's events
+    scan_event_sender: UnboundedSender<EventMessage>,
+    /// forwards the shown scan's events, dropping it stops them
+    scan_events_handle: Option<RemoteHandle<()>>,
     event_sender: UnboundedSender<EventMessage>,
 }
 
+// every component a plain name, so joining them can't leave the root
+fn is_tree_path(path: &[String]) -> bool {
+    path.iter().all(|name| {
+        let mut components = Path::new(name).components();
+        !name.contains(path::is_separator)
+            && matches!(components.next(), Some(Component::Normal(_)))
+            && components.next().is_none()
+    })
+}
+
 impl WebsocketHandler {
+    #[allow(clippy::too_many_lines)]
     async fn handle_message(&mut self, text: &str) {
-        let control_message: ControlMessage = serde_json::from_str(text).unwrap();
+        let control_message: ControlMessage = match serde_json::from_str(text) {
+            Ok(control_message) => control_message,
+            Err(e) => {
+                self.send_error(
+                    None,
+                    format!("invalid message: {}", e),
+                    ErrorKind::InvalidMessage,
+                )
+                .await;
+                return;
+            }
+        };
 
         match control_message {
+            ControlMessage::Volumes => {
+                self.send_volumes().await;
+            }
+
+            ControlMessage::ScanVolume { mount_point } => {
+                self.scan_volume(mount_point).await;
+            }
+
+            // everything else is about the shown scan
+            _ if self.thread_control_sender.is_none() => {
+                self.send_error(
+                    None,
+                    "pick a volume to scan first".to_string(),
+                    ErrorKind::Unsupported,
+                )
+                .await;
+            }
+
+            ControlMessage::ChangeDirectory { path }
+            | ControlMessage::Rescan { path }
+            | ControlMessage::Delete { path }
+            | ControlMessage::Reveal { path }
+                if !is_tree_path(&path) =>
+            {
+                self.send_error(
+                    Some(path),
+                    "not a path in the tree".to_string(),
+                    ErrorKind::InvalidMessage,
+                )
+                .await;
+            }
+
             ControlMessage::ChangeDirectory { path } => {
                 self.change_dir(path).await;
             }
 
+            ControlMessage::LargestFiles { count } => {
+                self.send_control(ScannerControlMessage::LargestFiles(count))
+                    .await;
+            }
+
+            ControlMessage::Extensions { subscribe } => {
+                self.send_control(ScannerControlMessage::Extensions(subscribe))
+                    .await;
+            }
+
+            ControlMessage::Owners { subscribe } => {
+                self.send_control(ScannerControlMessage::Owners(subscribe))
+                    .await;
+            }
+
+            ControlMessage::FindDuplicates if self.read_only => {
+                self.send_error(
+                    None,
+                    "can't search an imported scan for duplicates".to_string(),
+                    ErrorKind::Unsupported,
+                )
+                .await;
+            }
+
+            ControlMessage::FindDuplicates => {
+                self.send_control(ScannerControlMessage::FindDuplicates)
+                    .await;
+            }
+
+            ControlMessage::Rescan { path } if self.read_only => {
+                self.send_error(
+                    Some(path),
+                    "can't rescan an imported scan".to_string(),
+                    ErrorKind::Unsupported,
+                )
+                .await;
+            }
+
+            ControlMessage::Rescan { path } => {
+                self.send_control(ScannerControlMessage::Rescan(path)).await;
+            }
+
+            ControlMessage::Delete { path } if self.read_only => {
+                self.send_error(
+                    Some(path),
+                    "can't delete from an imported scan".to_string(),
+                    ErrorKind::Unsupported,
+                )
+                .await;
+            }
+
+            ControlMessage::Reveal { path } if self.read_only => {
+                self.send_error(
+                    Some(path),
+                    "can't reveal from an imported scan".to_string(),
+                    ErrorKind::Unsupported,
+                )
+                .await;
+            }
+
+            ControlMessage::Delete { path } | ControlMessage::Reveal { path }
+                if self.roots.full_path(&path).is_none() =>
+            {
+                self.send_error(
+                    Some(path),
+                    "the top level of several paths isn't on disk".to_string(),
+                    ErrorKind::Unsupported,
+                )
+                .await;
+            }
+
             ControlMessage::Delete { path } => {
                 if let Err(err) = self.delete(path.clone()).await {
-                    warn!("couldn't delete path {:?}: {}", path, err);
+                    self.send_error(
+                        Some(path),
+                        format!("couldn't delete: {}", err),
+                        err.downcast_ref::<io::Error>()
+                            .map_or(ErrorKind::Other, ErrorKind::from),
+                    )
+                    .await;
                 }
             }
 
             ControlMessage::Reveal { path } => {
-                let full_path: PathBuf = self.root_path.iter().cloned().chain(path).collect();
-                debug!("Reveal {:?}", full_path);
-
-                thread::spawn(move || {
-                    if let Err(err) = reveal::that(&full_path) {
-                        // TODO make these show in the browser
-                        warn!("couldn't reveal path {:?}: {}", full_path, err);
-                    }
-                });
+                self.reveal(path);
             }
         }
     }
 
+    fn reveal(&self, path: Vec<String>) {
+        let full_path = self.full_path(&path);
+        debug!("Reveal {:?}", full_path);
+
+        let event_sender = self.event_sender.clone();
+        thread::spawn(move || {
+            if let Err(err) = reveal::that(&full_path) {
+                warn!("couldn't reveal path {:?}: {}", full_path, err);
+
+                if let Err(e) = event_sender.unbounded_send(EventMessage::Error {
+                    path: Some(path),
+                    message: format!("couldn't reveal: {}", err),
+                    kind: ErrorKind::Other,
+                }) {
+                    warn!("reveal error to event_sender: {}", e);
+                }
+            }
+        });
+    }
+
     async fn change_dir(&mut self, path: Vec<String>) {
         self.current_dir = path.clone();
 
-        self.thread_control_sender
-            .send(ScannerControlMessage::ChangeDirectory(path))
+        self.send_control(ScannerControlMessage::ChangeDirectory(path))
+            .await;
+    }
+
+    async fn send_control(&mut self, control_message: ScannerControlMessage) {
+        if let Some(thread_control_sender) = &mut self.thread_control_sender {
+            if let Err(e) = thread_control_sender.send(control_message).await {
+                // the scanner only stops when it panics
+                self.send_error(None, format!("scanner stopped: {}", e), ErrorKind::Other)
+                    .await;
+            }
+        }
+    }
+
+    async fn send_volumes(&mut self) {
+        match list_volumes().await {
+            Ok(volumes) => {
+                if let Err(e) = self.send_event(EventMessage::Volumes { volumes }).await {
+                    warn!("send Volumes: {}", e);
+                }
+            }
+
+            Err(err) => {
+                self.send_error(
+                    None,
+                    format!("couldn't list volumes: {}", err),
+                    err.downcast_ref::<io::Error>()
+                        .map_or(ErrorKind::Other, ErrorKind::from),
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn scan_volume(&mut self, mount_point: String) {
+        match self.volume_scan(&mount_point).await {
+            Ok(scan) => {
+                self.show(&scan).await;
+                // the last one stops after this one is shown
+                self.volume_scan = Some(scan);
+            }
+
+            Err(err) => {
+                self.send_error(
+                    None,
+                    format!("couldn't scan {}: {}", mount_point, err),
+                    err.downcast_ref::<io::Error>()
+                        .map_or(ErrorKind::Other, ErrorKind::from),
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn volume_scan(&self, mount_point: &str) -> Result<Arc<Scan>, Error> {
+        // not just any path the client likes
+        let volumes = list_volumes().await?;
+        if !volumes
+            .iter()
+            .any(|volume| volume.mount_point == mount_point)
+        {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not a mounted filesystem").into());
+        }
+
+        info!("scan volume {:?}", mount_point);
+        Ok(self.service.volume(PathBuf::from(mount_point)).await)
+    }
+
+    /// Switch to `scan`, the client starts over at its top level.
+    async fn show(&mut self, scan: &Scan) {
+        let (roots, read_only, thread_control_sender, mut event_receiver) = match scan {
+            Scan::Live { roots, scanner } => {
+                let (thread_control_sender, event_receiver) = scanner.connect();
+
+                (roots.clone(), false, thread_control_sender, event_receiver)
+            }
+
+            Scan::Imported(snapshot) => {
+                let (thread_control_sender, thread_control_receiver) = unbounded_stream();
+
+                (
+                    Roots::new(vec![snapshot.root_path.clone()]),
+                    true,
+                    thread_control_sender,
+                    spawn_snapshot_stream(snapshot.clone(), thread_control_receiver),
+                )
+            }
+        };
+
+        let mut scan_event_sender = self.scan_event_sender.clone();
+        let (forward_future, forward_handle) = async move {
+            while let Some(event) = event_receiver.next().await {
+                if let Err(e) = scan_event_sender.send(event).await {
+                    warn!("forward scan event: {}", e);
+                    break;
+                }
+            }
+        }
+        .remote_handle();
+        tokio::spawn(forward_future);
+
+        // also disconnects from the last scan
+        self.thread_control_sender = Some(thread_control_sender);
+        self.scan_events_handle = Some(forward_handle);
+        self.current_dir = Vec::new();
+        self.read_only = read_only;
+        let root_path = roots.root_path();
+        self.roots = roots;
+
+        // after anything left from the last scan
+        if let Err(e) = self
+            .scan_event_sender
+            .send(EventMessage::SessionInfo {
+                root_path,
+                read_only,
+            })
             .await
-            .unwrap();
+        {
+            warn!("send SessionInfo: {}", e);
+        }
+    }
+
+    /// Only for paths checked to be on disk.
+    fn full_path(&self, path: &[String]) -> PathBuf {
+        self.roots
+            .full_path(path)
+            .expect("path under the virtual top level")
     }
 
     async fn delete(&mut self, path: Vec<String>) -> Result<(), Error> {
-        let full_path: PathBuf = self.root_path.iter().cloned().chain(path.clone()).collect();
+        let full_path = self.full_path(&path);
         info!("delete {:?}", full_path);
 
         // TODO should this just go in the js?
@@ -85,7 +499,7 @@ impl WebsocketHandler {
                     fs::remove_file(full_path)?;
                 }
 
-                Ok::<_, std::io::Error>(())
+                Ok::<_, io::Error>(())
             }),
         )
         .await;
@@ -94,18 +508,19 @@ impl WebsocketHandler {
             Either::Left((_, remove_future)) => {
                 // we're taking a long time, start notifying client
 
-                self.send_event(EventMessage::Deleting {
-                    path: path.clone(),
-                    status: DeletingStatus::Deleting,
-                })
-                .await
-                .with_context(|_| "self.send_event()")
-                .unwrap();
+                if let Err(e) = self
+                    .send_event(EventMessage::Deleting {
+                        path: path.clone(),
+                        status: DeletingStatus::Deleting,
+                    })
+                    .await
+                {
+                    warn!("send Deleting: {}", e);
+                }
 
                 remove_future
                     .await
-                    .with_context(|_| "remove_future panic?")
-                    .unwrap()?;
+                    .with_context(|_| "remove_future panic?")??;
             }
 
             Either::Right((ret, _ignore_delay_future)) => {
@@ -113,18 +528,24 @@ impl WebsocketHandler {
 
                 // stop timer
                 drop(delay_remote_handle);
-                ret.with_context(|_| "remove_future panic?").unwrap()?;
+                ret.with_context(|_| "remove_future panic?")??;
             }
         }
 
         // always tell the client we're done deleting
-        self.send_event(EventMessage::Deleting {
-            path,
-            status: DeletingStatus::Finished,
-        })
-        .await
-        .unwrap();
+        if let Err(e) = self
+            .send_event(EventMessage::Deleting {
+                path: path.clone(),
+                status: DeletingStatus::Finished,
+            })
+            .await
+        {
+            warn!("send Deleting: {}", e);
+        }
 
+        // takes it out of the totals, which waits for any walk to finish
+        self.send_control(ScannerControlMessage::Rescan(path)).await;
+        // while it's already gone from the listing
         self.refresh().await;
 
         Ok(())
@@ -143,28 +564,48 @@ impl WebsocketHandler {
         Ok(())
     }
 
-    pub async fn run(root_path: &PathBuf, ws: warp::ws::WebSocket) {
-        info!("ws started");
-
-        let root_path = get_components(root_path);
+    async fn send_error(&mut self, path: Option<Vec<String>>, message: String, kind: ErrorKind) {
+        warn!("{:?}: {}", path, message);
 
-        // scanner -> size_update -> ws_sender
+        if let Err(e) = self
+            .send_event(EventMessage::Error {
+                path,
+                message,
+                kind,
+            })
+            .await
+        {
+            warn!("send Error: {}", e);
+        }
+    }
 
-        let (thread_control_sender, thread_control_receiver) = unbounded_stream();
+    pub async fn run(service: &ScanService, ws: warp::ws::WebSocket) {
+        info!("ws started");
 
-        let event_receiver = spawn_scanner_stream(root_path.clone(), thread_control_receiver).await;
+        // scanner -> size_update -> ws_sender
 
-        let (event_sender, mut event_receiver) = spawn_size_update_stream(event_receiver);
+        let (scan_event_sender, scan_event_receiver) = unbounded_stream();
+        let (event_sender, mut event_receiver) = spawn_size_update_stream(scan_event_receiver);
 
         let (mut ws_sender, mut ws_receiver) = ws.split();
 
         let mut handler = WebsocketHandler {
-            root_path,
+            service: service.clone(),
+            roots: Roots::new(Vec::new()),
+            read_only: false,
             current_dir: Vec::new(),
-            thread_control_sender,
+            thread_control_sender: None,
+            volume_scan: None,
+            scan_event_sender,
+            scan_events_handle: None,
             event_sender,
         };
 
+        match &service.initial {
+            Some(scan) => handler.show(scan).await,
+            None => handler.send_volumes().await,
+        }
+
         let ws_sender_future = async move {
             while let Some(event) = event_receiver.next().await {
                 let s = serde_json::to_string(&event).unwrap();
@@ -227,24 +668,21 @@ fn spawn_size_update_stream(
 
         while let Some(event) = event_receiver.next().await {
             match &event {
-                EventMessage::DirectoryChange { .. } => {
+                EventMessage::DirectoryChange { .. } | EventMessage::SessionInfo { .. } => {
                     // clear maps, stop timers!!
                     sums_mutex.lock().await.clear();
                 }
 
                 EventMessage::SizeUpdate { entry } => {
                     // push to sums, send size update message every interval
-                    let (path, size, updating) = match entry {
-                        Entry::File { .. } => unreachable!(),
+                    let path = entry.path().to_vec();
+
+                    let slow_update = match entry {
                         Entry::Directory {
-                            path,
                             size,
-                            updating,
-                        } => (path.clone(), *size, *updating),
-                    };
-
-                    let slow_update = match updating {
-                        UpdatingStatus::Updating => size != 0,
+                            updating: UpdatingStatus::Updating,
+                            ..
+                        } => *size != 0,
                         _ => false,
                     };
 
@@ -256,9 +694,9 @@ fn spawn_size_update_stream(
                             .lock()
                             .await
                             .entry(path.clone())
-                            .and_modify(|(old_size, _remote_handle)| {
+                            .and_modify(|(old_entry, _remote_handle)| {
                                 // always modify size
-                                *old_size = size;
+                                *old_entry = entry.clone();
                             })
                             .or_insert_with(|| {
                                 // if no entry then start timer
@@ -276,17 +714,10 @@ fn spawn_size_update_stream(
                                         .upgrade()
                                         .expect("sums_mutex_weak.upgrade shouldn't happen??");
                                     let mut sums = sums_mutex.lock().await;
-                                    let (size, my_remote_handle) = sums.remove(&path).unwrap();
-
-                                    if let Err(e) = event_sender
-                                        .send(EventMessage::SizeUpdate {
-                                            entry: Entry::Directory {
-                                                path: path.clone(),
-                                                size,
-                                                updating,
-                                            },
-                                        })
-                                        .await
+                                    let (entry, my_remote_handle) = sums.remove(&path).unwrap();
+
+                                    if let Err(e) =
+                                        event_sender.send(EventMessage::SizeUpdate { entry }).await
                                     {
                                         warn!("timer size_update: {}", e);
                                     }
@@ -297,7 +728,7 @@ fn spawn_size_update_stream(
                                 // we will drop remote_handle (and stop the timer) on dir change "sums.clear()"
                                 tokio::spawn(fut);
 
-                                (size, remote_handle)
+                                (entry.clone(), remote_handle)
                             });
 
                         // don't send this message
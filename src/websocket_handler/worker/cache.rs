@@ -0,0 +1,397 @@
+//! The last scan of each root, so the next one only has to look at what changed.
+
+use super::{
+    get_components, unix_time, walk_directory, walk_part, DirStatus, Directory, FileType,
+    HardLinks, Roots, Snapshot, SnapshotFormat, WalkOptions,
+};
+use failure::Error;
+use log::{debug, info, warn};
+use std::{
+    collections::HashSet,
+    env, fs, mem,
+    path::{Path, PathBuf},
+    process, thread,
+};
+
+#[derive(Debug)]
+pub enum ValidateEvent {
+    /// paths relative to the root like a normal walk
+    FileType(FileType),
+    /// something was added or removed here,
+    /// forget what's directly inside before the new `FileType`s
+    Reread(Vec<String>),
+    /// up to date, with its `modified` now
+    Validated(Vec<String>, Option<u64>),
+    /// doesn't exist anymore
+    Gone(Vec<String>),
+    Finished,
+}
+
+impl ValidateEvent {
+    /// For a root under the virtual top level.
+    pub fn add_prefix(&mut self, prefix: &[String]) {
+        match self {
+            Self::FileType(file_type) => file_type.add_prefix(&prefix.iter().collect::<PathBuf>()),
+            Self::Reread(path) | Self::Validated(path, _) | Self::Gone(path) => {
+                path.splice(0..0, prefix.iter().cloned());
+            }
+            Self::Finished => {}
+        }
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    #[cfg(windows)]
+    let base = env::var_os("LOCALAPPDATA").map(PathBuf::from);
+
+    #[cfg(not(windows))]
+    let base = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")));
+
+    base.unwrap_or_else(env::temp_dir)
+        .join(env!("CARGO_PKG_NAME"))
+}
+
+// one file per root and options that change what's walked,
+// named by a hash since paths can't be file names
+fn cache_file(cache_dir: &Path, root_path: &Path, walk_options: &WalkOptions) -> PathBuf {
+    let root_path = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(root_path.to_string_lossy().as_bytes());
+    hasher.update(&[
+        0,
+        u8::from(walk_options.one_file_system),
+        u8::from(walk_options.follow_symlinks),
+    ]);
+    for pattern in &walk_options.exclude_patterns {
+        hasher.update(&[0]);
+        hasher.update(pattern.as_bytes());
+    }
+
+    cache_dir.join(format!("{}.json", hasher.finalize().to_hex()))
+}
+
+/// The tree of `root_path` cached with the same `walk_options`,
+/// None if there isn't one or it can't be read.
+pub fn load_cache(root_path: &Path, walk_options: &WalkOptions) -> Option<Directory> {
+    load_cache_from(&cache_dir(), root_path, walk_options)
+}
+
+fn load_cache_from(
+    cache_dir: &Path,
+    root_path: &Path,
+    walk_options: &WalkOptions,
+) -> Option<Directory> {
+    let file_path = cache_file(cache_dir, root_path, walk_options);
+    if !file_path.exists() {
+        debug!("no cache for {:?}", root_path);
+        return None;
+    }
+
+    match Snapshot::read(&file_path) {
+        Ok(snapshot) if snapshot.root_path == root_path => {
+            info!("loaded cache {:?}", file_path);
+
+            // files got older since
+            let mut tree = snapshot.tree;
+            tree.recount_ages();
+            Some(tree)
+        }
+
+        Ok(snapshot) => {
+            warn!(
+                "cache {:?} is for {:?} not {:?}",
+                file_path, snapshot.root_path, root_path
+            );
+            None
+        }
+
+        Err(e) => {
+            warn!("couldn't read cache {:?}: {}", file_path, e);
+            None
+        }
+    }
+}
+
+/// Without any `--compare` sizes, `compare` again after saving to keep them.
+pub fn save_cache(root_path: &Path, walk_options: &WalkOptions, tree: &mut Directory) {
+    save_cache_to(&cache_dir(), root_path, walk_options, tree);
+}
+
+/// Each root in its own file, so it's found again however it's scanned next time.
+pub fn save_caches(roots: &Roots, walk_options: &WalkOptions, tree: &mut Directory) {
+    for (root, root_path) in roots.iter() {
+        if let Some(dir) = tree.at_mut(root) {
+            save_cache(root_path, walk_options, dir);
+        }
+    }
+}
+
+fn save_cache_to(
+    cache_dir: &Path,
+    root_path: &Path,
+    walk_options: &WalkOptions,
+    tree: &mut Directory,
+) {
+    let file_path = cache_file(cache_dir, root_path, walk_options);
+    // they'd show up as changes next time even without --compare
+    tree.clear_comparison();
+
+    // put back below, a Snapshot owns its tree
+    let snapshot = Snapshot {
+        root_path: root_path.to_path_buf(),
+        tree: mem::take(tree),
+    };
+
+    // renamed over the old one once it's all there, so a crash or another
+    // scan of the same root never leaves half a cache
+    let temp_path = file_path.with_extension(format!("{}.tmp", process::id()));
+    let result: Result<(), Error> = fs::create_dir_all(cache_dir)
+        .map_err(Error::from)
+        .and_then(|()| snapshot.write(&temp_path, SnapshotFormat::Json))
+        .and_then(|()| fs::rename(&temp_path, &file_path).map_err(Error::from));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    match result {
+        Ok(()) => info!("saved cache {:?}", file_path),
+        Err(e) => warn!("couldn't save cache {:?}: {}", file_path, e),
+    }
+
+    *tree = snapshot.tree;
+}
+
+/// Check every directory from `Directory::directory_modified` on its own thread,
+/// until `send_event` returns false.
+/// Only directories whose `modified` changed are read again,
+/// `hard_links` of the cached tree keeps up with it to tell which files are counted
+/// along with the ones `elsewhere` in other roots.
+pub fn spawn_validate<F>(
+    root_path: PathBuf,
+    directories: Vec<(Vec<String>, Option<u64>)>,
+    mut hard_links: HardLinks,
+    elsewhere: HashSet<(u64, u64)>,
+    walk_options: WalkOptions,
+    mut send_event: F,
+) where
+    F: FnMut(ValidateEvent) -> bool + Send + 'static,
+{
+    thread::spawn(move || {
+        let cached: HashSet<&[String]> = directories.iter().map(|(path, _)| &path[..]).collect();
+        let mut gone: Vec<&[String]> = Vec::new();
+
+        for (path, cached_modified) in &directories {
+            // parents come first
+            if gone.iter().any(|gone| path.starts_with(gone)) {
+                continue;
+            }
+
+            let full_path = root_path.join(path.iter().collect::<PathBuf>());
+            let modified = match fs::metadata(&full_path) {
+                Ok(metadata) if metadata.is_dir() => unix_time(metadata.modified()),
+                _ => {
+                    gone.push(path);
+                    hard_links.remove_below(path);
+                    if !send_event(ValidateEvent::Gone(path.clone())) {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            if modified.is_none() || modified != *cached_modified {
+                debug!("{:?} changed", full_path);
+                if !send_event(ValidateEvent::Reread(path.clone()))
+                    || !reread(
+                        &root_path,
+                        path,
+                        &cached,
+                        &mut hard_links,
+                        &elsewhere,
+                        &walk_options,
+                        &mut send_event,
+                    )
+                {
+                    return;
+                }
+            }
+
+            if !send_event(ValidateEvent::Validated(path.clone(), modified)) {
+                return;
+            }
+        }
+
+        send_event(ValidateEvent::Finished);
+    });
+}
+
+// what's directly in `path`, and all of any new directories,
+// with the same links counted as `Directory::remove_files` leaves
+fn reread<F>(
+    root_path: &Path,
+    path: &[String],
+    cached: &HashSet<&[String]>,
+    hard_links: &mut HardLinks,
+    elsewhere: &HashSet<(u64, u64)>,
+    walk_options: &WalkOptions,
+    send_event: &mut F,
+) -> bool
+where
+    F: FnMut(ValidateEvent) -> bool,
+{
+    let full_path = root_path.join(path.iter().collect::<PathBuf>());
+    let prefix: PathBuf = path.iter().collect();
+    let counted = |hard_links: &HardLinks| {
+        let mut counted = hard_links.counted();
+        counted.extend(elsewhere);
+        counted
+    };
+
+    hard_links.remove_in(path);
+    let mut new_dirs = Vec::new();
+
+    for mut file_type in walk_directory(full_path, walk_options, counted(hard_links)) {
+        file_type.add_prefix(&prefix);
+
+        match file_type {
+            // cached directories are checked on their own
+            FileType::Dir(dir_path, DirStatus::Started(_))
+                if !cached.contains(&get_components(&dir_path)[..]) =>
+            {
+                new_dirs.push(dir_path);
+            }
+
+            FileType::Dir(..) => {}
+
+            file_type => {
+                hard_links.update(&file_type);
+                if !send_event(ValidateEvent::FileType(file_type)) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    // after the files, which might be links to the same ones
+    for dir_path in new_dirs {
+        let counted = counted(hard_links);
+        for mut file_type in walk_part(root_path.join(&dir_path), walk_options, counted) {
+            file_type.add_prefix(&dir_path);
+            hard_links.update(&file_type);
+            if !send_event(ValidateEvent::FileType(file_type)) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+#[test]
+fn test_cache() {
+    use super::{DirStatus, FileSize, FileType};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let cache_dir = temp_dir.path().join("cache");
+    let root_path = Path::new("test-folder");
+
+    let walk_options = WalkOptions::default();
+    assert!(load_cache_from(&cache_dir, root_path, &walk_options).is_none());
+
+    let now = unix_time(Ok(std::time::SystemTime::now())).unwrap();
+
+    let mut tree = Directory::new();
+    tree.update(&FileType::Dir("".into(), DirStatus::Started(Some(1000))));
+    tree.update(&FileType::File(FileSize {
+        path: "a".into(),
+        size: 10,
+        modified: Some(now),
+        ..FileSize::default()
+    }));
+    tree.compare(&Directory::new());
+    // as if it was scanned long ago
+    tree.age_histogram.0 = [0, 0, 0, 0, 10];
+    save_cache_to(&cache_dir, root_path, &walk_options, &mut tree);
+    // without the file it was written to first
+    assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+    // what was walked would be different
+    let other_options = [
+        WalkOptions {
+            one_file_system: true,
+            ..WalkOptions::default()
+        },
+        WalkOptions {
+            exclude_patterns: vec!["a".to_string()],
+            ..WalkOptions::default()
+        },
+    ];
+    for other_options in &other_options {
+        assert!(load_cache_from(&cache_dir, root_path, other_options).is_none());
+    }
+
+    let tree = load_cache_from(&cache_dir, root_path, &walk_options).unwrap();
+
+    assert_eq!(tree.total_size, 10);
+    assert_eq!(tree.modified, Some(1000));
+    assert_eq!(tree.previous_size, None);
+    assert_eq!(tree.file("a").unwrap().previous_size, None);
+    assert_eq!(tree.age_histogram.0, [10, 0, 0, 0, 0]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_validate_hard_links() {
+    use std::sync::mpsc::channel;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path().to_path_buf();
+    fs::create_dir(root.join("a")).unwrap();
+    fs::create_dir(root.join("b")).unwrap();
+    fs::write(root.join("a/x"), "hello").unwrap();
+    fs::hard_link(root.join("a/x"), root.join("b/y")).unwrap();
+
+    let walk_options = WalkOptions::default();
+    let mut tree = Directory::scan(root.clone(), &walk_options);
+    let mut hard_links = HardLinks::from_tree(&tree);
+
+    // both look changed, the counted link goes back and forth
+    let directories = tree
+        .directory_modified()
+        .into_iter()
+        .map(|(path, _)| (path, Some(0)))
+        .collect();
+    let (sender, receiver) = channel();
+    spawn_validate(
+        root.clone(),
+        directories,
+        hard_links.clone(),
+        HashSet::new(),
+        walk_options,
+        move |event| sender.send(event).is_ok(),
+    );
+
+    for event in receiver {
+        match event {
+            ValidateEvent::FileType(file_type) => {
+                tree.update(&file_type);
+                hard_links.update(&file_type);
+            }
+            ValidateEvent::Reread(path) => tree.remove_files(&path, &mut hard_links),
+            ValidateEvent::Gone(path) => {
+                tree.remove_linked(&path, &mut hard_links);
+            }
+            ValidateEvent::Validated(path, modified) => tree.set_validated(&path, modified),
+            ValidateEvent::Finished => break,
+        }
+    }
+
+    assert_eq!(
+        (tree.total_size, tree.file_count, tree.hard_links),
+        (5, 1, 1)
+    );
+}
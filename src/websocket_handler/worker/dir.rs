@@ -1,52 +1,96 @@
-use super::{get_components, Directory, Entry};
-use std::{fs, path::PathBuf};
+use super::{allocated_size, unix_time, Directory, Entry};
+use crate::websocket_handler::api::size_change;
+use log::warn;
+use std::{fs, io, path::PathBuf};
 
 pub fn get_directory_entries(
-    root_path: &[String],
+    full_path: PathBuf,
     path: &[String],
-    tree: &mut Directory,
-) -> (Vec<Entry>, u64) {
-    // root_path: ["src"]
+    tree: &Directory,
+) -> io::Result<Vec<Entry>> {
+    // full_path: src/web_server/websocket_actor
     // path: ["web_server", "websocket_actor"]
 
-    // src/web_server/websocket_actor
-    let full_path: PathBuf = root_path.iter().chain(path).collect();
-    let root_path: PathBuf = root_path.iter().collect();
+    let parent = tree.at(path);
 
-    let entries = fs::read_dir(&full_path)
-        .expect("read_dir")
-        .map(move |maybe_entry| {
-            let entry = maybe_entry.expect("maybe_entry");
+    let entries = fs::read_dir(&full_path)?
+        .filter_map(move |maybe_entry| {
+            let entry = match maybe_entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("couldn't read entry in {:?}: {}", full_path, e);
+                    return None;
+                }
+            };
+
+            let mut relative_path = path.to_vec();
+            relative_path.push(entry.file_name().to_string_lossy().to_string());
 
-            let path = entry.path();
+            let unreadable = |e: std::io::Error| Entry::Error {
+                path: relative_path.clone(),
+                message: e.to_string(),
+            };
 
-            let file_type = entry.file_type().expect("file_type");
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => return Some(unreadable(e)),
+            };
 
-            let relative_path = get_components(
-                path.strip_prefix(&root_path)
-                    .expect("strip_prefix")
-                    .to_owned(),
-            );
+            // a followed symlink is whatever the scan made of its target
+            let followed_dir = file_type.is_symlink() && tree.at(&relative_path).is_some();
+            let followed_file = file_type.is_symlink()
+                && parent
+                    .and_then(|parent| parent.file(relative_path.last()?))
+                    .is_some();
 
-            if file_type.is_dir() {
+            Some(if file_type.is_dir() || followed_dir {
                 tree.get_entry_directory(relative_path)
-            } else {
-                // TODO symlinks as own Entry
+            } else if file_type.is_symlink() && !followed_file {
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(e) => return Some(unreadable(e)),
+                };
+                let target = match fs::read_link(entry.path()) {
+                    Ok(target) => target,
+                    Err(e) => return Some(unreadable(e)),
+                };
 
-                let metadata = entry.metadata().expect("metadata");
+                Entry::Symlink {
+                    path: relative_path,
+                    target: target.to_string_lossy().to_string(),
+                    size: metadata.len(),
+                }
+            } else if !file_type.is_file() && !file_type.is_symlink() {
+                Entry::Special {
+                    path: relative_path,
+                    kind: file_type.into(),
+                }
+            } else {
+                // follows symlinks
+                let metadata = match fs::metadata(entry.path()) {
+                    Ok(metadata) => metadata,
+                    Err(e) => return Some(unreadable(e)),
+                };
                 let size = metadata.len();
 
+                let previous_size = parent
+                    .and_then(|parent| parent.file(relative_path.last()?))
+                    .and_then(|file| file.previous_size);
+
                 Entry::File {
                     path: relative_path,
                     size,
+                    allocated_size: allocated_size(&metadata),
+                    previous_size,
+                    size_change: size_change(size, previous_size),
+                    modified: unix_time(metadata.modified()),
+                    accessed: unix_time(metadata.accessed()),
                 }
-            }
+            })
         })
         .collect();
 
-    let available_space = fs2::available_space(&full_path).unwrap();
-
-    (entries, available_space)
+    Ok(entries)
 }
 
 // #[test]
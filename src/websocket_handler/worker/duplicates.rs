@@ -0,0 +1,207 @@
+use crate::websocket_handler::{
+    api::{DuplicateGroup, EventMessage},
+    worker::{roots::Roots, tree::Directory},
+};
+use futures::channel::mpsc::UnboundedSender;
+use log::{debug, info, warn};
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    thread,
+    time::Instant,
+};
+
+/// bytes hashed from the start of each file before hashing all of it
+const PARTIAL_HASH_SIZE: u64 = 4096;
+
+/// Groups of same-size files, the only ones that could be duplicates.
+/// Hard links are left out since they already share their data.
+pub fn get_duplicate_candidates(tree: &Directory) -> Vec<(u64, Vec<Vec<String>>)> {
+    let mut by_size = HashMap::new();
+    add_directory(tree, &mut Vec::new(), &mut by_size);
+
+    by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .collect()
+}
+
+fn add_directory(
+    dir: &Directory,
+    path: &mut Vec<String>,
+    by_size: &mut HashMap<u64, Vec<Vec<String>>>,
+) {
+    for (name, file) in dir.files() {
+        // empty files are all the same but nothing is gained by removing them
+        if file.hard_link || file.size == 0 {
+            continue;
+        }
+
+        let mut file_path = path.clone();
+        file_path.push(name.clone());
+        by_size.entry(file.size).or_default().push(file_path);
+    }
+
+    for (name, child) in dir.entries() {
+        path.push(name.clone());
+        add_directory(child, path, by_size);
+        path.pop();
+    }
+}
+
+fn hash_file(path: &Path, limit: Option<u64>) -> io::Result<blake3::Hash> {
+    let file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+
+    match limit {
+        Some(limit) => io::copy(&mut file.take(limit), &mut hasher)?,
+        None => io::copy(&mut io::BufReader::new(file), &mut hasher)?,
+    };
+
+    Ok(hasher.finalize())
+}
+
+/// Split `paths` by content hash, dropping groups of one.
+fn group_by_hash(
+    roots: &Roots,
+    paths: Vec<Vec<String>>,
+    limit: Option<u64>,
+) -> Vec<Vec<Vec<String>>> {
+    let hashes: Vec<(blake3::Hash, Vec<String>)> = paths
+        .into_par_iter()
+        .filter_map(|path| {
+            let full_path = roots.full_path(&path)?;
+            match hash_file(&full_path, limit) {
+                Ok(hash) => Some((hash, path)),
+                Err(e) => {
+                    warn!("couldn't hash {:?}: {}", full_path, e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let mut groups: HashMap<blake3::Hash, Vec<Vec<String>>> = HashMap::new();
+    for (hash, path) in hashes {
+        groups.entry(hash).or_default().push(path);
+    }
+
+    groups
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .collect()
+}
+
+/// Confirm candidates by hashing the start of each file,
+/// then the whole thing for those still matching.
+/// Biggest savings first.
+pub fn find_duplicates(
+    roots: &Roots,
+    candidates: Vec<(u64, Vec<Vec<String>>)>,
+) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<DuplicateGroup> = candidates
+        .into_par_iter()
+        .flat_map(|(size, paths)| {
+            let partial_groups = group_by_hash(roots, paths, Some(PARTIAL_HASH_SIZE));
+
+            let groups = if size <= PARTIAL_HASH_SIZE {
+                // already read the whole file
+                partial_groups
+            } else {
+                partial_groups
+                    .into_iter()
+                    .flat_map(|paths| group_by_hash(roots, paths, None))
+                    .collect()
+            };
+
+            groups
+                .into_iter()
+                .map(|mut paths| {
+                    paths.sort();
+                    DuplicateGroup { size, paths }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    groups.sort_unstable_by(|a, b| {
+        b.reclaimable_size()
+            .cmp(&a.reclaimable_size())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    groups
+}
+
+/// Hash on another thread so the scanner can keep answering directory changes.
+pub fn spawn_duplicate_search(
+    roots: Roots,
+    tree: &Directory,
+    event_sender: UnboundedSender<EventMessage>,
+) {
+    let candidates = get_duplicate_candidates(tree);
+    debug!("{} sizes with duplicate candidates", candidates.len());
+
+    thread::spawn(move || {
+        let start_time = Instant::now();
+
+        let groups = find_duplicates(&roots, candidates);
+        let reclaimable_size = groups.iter().map(DuplicateGroup::reclaimable_size).sum();
+
+        info!("duplicate search done! {:?}", start_time.elapsed());
+
+        if let Err(e) = event_sender.unbounded_send(EventMessage::Duplicates {
+            groups,
+            reclaimable_size,
+        }) {
+            warn!("duplicates to event_sender: {}", e);
+        }
+    });
+}
+
+#[test]
+fn test_find_duplicates() {
+    use super::walker::WalkOptions;
+    use std::fs;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root_path = temp_dir.path().to_path_buf();
+    fs::create_dir(root_path.join("a")).unwrap();
+
+    let big = vec![7u8; 2 * 4096];
+    let mut big_changed_end = big.clone();
+    *big_changed_end.last_mut().unwrap() = 8;
+
+    fs::write(root_path.join("small-1"), "hello").unwrap();
+    fs::write(root_path.join("a/small-2"), "hello").unwrap();
+    fs::write(root_path.join("a/small-3"), "world").unwrap();
+    fs::write(root_path.join("big-1"), &big).unwrap();
+    fs::write(root_path.join("a/big-2"), &big).unwrap();
+    fs::write(root_path.join("a/big-3"), &big_changed_end).unwrap();
+
+    let tree = Directory::scan(root_path.clone(), &WalkOptions::default());
+    let groups = find_duplicates(
+        &Roots::new(vec![root_path]),
+        get_duplicate_candidates(&tree),
+    );
+
+    let groups: Vec<(u64, Vec<String>)> = groups
+        .into_iter()
+        .map(|group| {
+            let paths = group.paths.iter().map(|path| path.join("/")).collect();
+            (group.size, paths)
+        })
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            (
+                big.len() as u64,
+                vec!["a/big-2".to_string(), "big-1".to_string()]
+            ),
+            (5, vec!["a/small-2".to_string(), "small-1".to_string()]),
+        ]
+    );
+}
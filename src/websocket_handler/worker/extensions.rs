@@ -0,0 +1,84 @@
+use crate::websocket_handler::{api::ExtensionSize, worker::tree::Directory};
+use std::path::Path;
+
+/// Lowercase extension, empty for none.
+pub fn get_extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Totals per file extension for everything under `dir`, biggest first.
+pub fn get_extensions(dir: &Directory) -> Vec<ExtensionSize> {
+    let mut extensions: Vec<ExtensionSize> = dir
+        .extensions
+        .iter()
+        .map(|(extension, total)| ExtensionSize {
+            extension: extension.clone(),
+            size: total.size,
+            allocated_size: total.allocated_size,
+            file_count: total.file_count,
+        })
+        .collect();
+
+    extensions.sort_unstable_by(|a, b| {
+        b.size
+            .cmp(&a.size)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+    extensions
+}
+
+#[test]
+fn test_get_extensions() {
+    use super::walker::{DirStatus, FileSize, FileType};
+
+    let mut tree = Directory::new();
+    for path in &["", "a", "a/b"] {
+        tree.update(&FileType::Dir(path.into(), DirStatus::Started(None)));
+    }
+    for (path, size) in &[
+        ("a/video.MKV", 100),
+        ("a/b/other.mkv", 50),
+        ("a/b/log.txt", 10),
+        ("a/Makefile", 1),
+        ("a/.bashrc", 2),
+    ] {
+        tree.update(&FileType::File(FileSize {
+            path: path.into(),
+            size: *size,
+            allocated_size: *size,
+            ..FileSize::default()
+        }));
+    }
+
+    let summary = |dir: &Directory| -> Vec<(String, u64, u64)> {
+        get_extensions(dir)
+            .into_iter()
+            .map(|extension| (extension.extension, extension.size, extension.file_count))
+            .collect()
+    };
+    let expected = [
+        ("mkv".to_string(), 150, 2),
+        ("txt".to_string(), 10, 1),
+        (String::new(), 3, 2),
+    ];
+    assert_eq!(summary(&tree), expected);
+
+    let b = tree.at(&["a".to_string(), "b".to_string()]).unwrap();
+    assert_eq!(get_extensions(b).len(), 2);
+
+    // they aren't saved
+    let mut read_back: Directory =
+        serde_json::from_str(&serde_json::to_string(&tree).unwrap()).unwrap();
+    assert!(get_extensions(&read_back).is_empty());
+    read_back.recount_extensions();
+    assert_eq!(summary(&read_back), expected);
+
+    tree.remove(&["a".to_string(), "b".to_string()]);
+    assert_eq!(
+        summary(&tree),
+        [("mkv".to_string(), 100, 1), expected[2].clone()]
+    );
+}
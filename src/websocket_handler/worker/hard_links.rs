@@ -0,0 +1,155 @@
+use crate::websocket_handler::worker::{
+    tree::{get_components, Directory},
+    walker::FileType,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Every path in the tree of each file that can be found more than once,
+/// so another link can be counted when the counted one is taken out.
+#[derive(Debug, Clone, Default)]
+pub struct HardLinks {
+    /// by (device, inode), the counted path first
+    paths: HashMap<(u64, u64), Vec<Vec<String>>>,
+}
+
+impl HardLinks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_tree(tree: &Directory) -> Self {
+        let mut hard_links = Self::new();
+        hard_links.add_directory(tree, &mut Vec::new());
+        hard_links
+    }
+
+    fn add_directory(&mut self, dir: &Directory, path: &mut Vec<String>) {
+        for (name, file) in dir.files() {
+            if let Some(inode) = file.inode {
+                let file_path = path.iter().cloned().chain(Some(name.clone())).collect();
+                self.add(inode, file_path, !file.hard_link);
+            }
+        }
+
+        for (name, child) in dir.entries() {
+            path.push(name.clone());
+            self.add_directory(child, path);
+            path.pop();
+        }
+    }
+
+    fn add(&mut self, inode: (u64, u64), path: Vec<String>, counted: bool) {
+        let paths = self.paths.entry(inode).or_default();
+        if counted {
+            paths.insert(0, path);
+        } else {
+            paths.push(path);
+        }
+    }
+
+    /// Keep up with the tree, call along with `Directory::update`.
+    pub fn update(&mut self, file_type: &FileType) {
+        if let FileType::File(file) | FileType::HardLink(file) = file_type {
+            if let Some(inode) = file.inode {
+                let counted = matches!(file_type, FileType::File(_));
+                self.add(inode, get_components(&file.path), counted);
+            }
+        }
+    }
+
+    /// (device, inode) of every file that's counted somewhere,
+    /// so walking part of the tree again doesn't count them twice.
+    pub fn counted(&self) -> HashSet<(u64, u64)> {
+        self.paths.keys().copied().collect()
+    }
+
+    /// (device, inode) of every file with a path outside `components`,
+    /// which stays counted whatever is taken out inside.
+    pub fn outside(&self, components: &[String]) -> HashSet<(u64, u64)> {
+        self.paths
+            .iter()
+            .filter(|(_, paths)| paths.iter().any(|path| !path.starts_with(components)))
+            .map(|(inode, _)| *inode)
+            .collect()
+    }
+
+    /// Forget every path at or below `components`, which was taken out of the tree.
+    /// Returns the links to count instead of the files that were counted there.
+    pub fn remove_below(&mut self, components: &[String]) -> Vec<Vec<String>> {
+        self.remove_where(|path| path.starts_with(components))
+    }
+
+    /// `remove_below` for just the files directly inside `components`.
+    pub fn remove_in(&mut self, components: &[String]) -> Vec<Vec<String>> {
+        self.remove_where(|path| path.len() == components.len() + 1 && path.starts_with(components))
+    }
+
+    fn remove_where<F: Fn(&[String]) -> bool>(&mut self, removed: F) -> Vec<Vec<String>> {
+        let mut promoted = Vec::new();
+
+        self.paths.retain(|_, paths| {
+            let counted_removed = matches!(paths.first(), Some(counted) if removed(counted));
+            paths.retain(|path| !removed(path));
+
+            if counted_removed {
+                promoted.extend(paths.first().cloned());
+            }
+            !paths.is_empty()
+        });
+
+        promoted
+    }
+}
+
+#[test]
+fn test_hard_links() {
+    use super::walker::{DirStatus, FileSize};
+
+    let mut tree = Directory::new();
+    let mut hard_links = HardLinks::new();
+    for path in &["", "a", "b", "c"] {
+        tree.update(&FileType::Dir(path.into(), DirStatus::Started(None)));
+    }
+    for (path, counted) in &[("a/1", true), ("b/2", false), ("c/3", false)] {
+        let file = FileSize {
+            path: path.into(),
+            size: 10,
+            inode: Some((1, 7)),
+            multiple_links: true,
+            ..FileSize::default()
+        };
+        let file_type = if *counted {
+            FileType::File(file)
+        } else {
+            FileType::HardLink(file)
+        };
+        tree.update(&file_type);
+        hard_links.update(&file_type);
+    }
+    assert_eq!((tree.total_size, tree.hard_links), (10, 2));
+    assert_eq!(
+        hard_links.counted().into_iter().collect::<Vec<_>>(),
+        [(1, 7)]
+    );
+
+    // another link is counted in its place
+    tree.remove_linked(&["a".to_string()], &mut hard_links);
+    assert_eq!((tree.total_size, tree.hard_links), (10, 1));
+    assert_eq!(tree.at(&["b".to_string()]).unwrap().total_size, 10);
+    assert!(
+        !tree
+            .at(&["b".to_string()])
+            .unwrap()
+            .file("2")
+            .unwrap()
+            .hard_link
+    );
+
+    // not counted there, nothing changes hands
+    tree.remove_linked(&["c".to_string()], &mut hard_links);
+    assert_eq!((tree.total_size, tree.hard_links), (10, 0));
+
+    tree.remove_linked(&["b".to_string()], &mut hard_links);
+    assert_eq!((tree.total_size, tree.file_count), (0, 0));
+    assert!(hard_links.counted().is_empty());
+}
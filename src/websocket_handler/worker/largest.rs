@@ -0,0 +1,192 @@
+use crate::websocket_handler::{
+    api::{size_change, Entry},
+    worker::{
+        tree::{get_components, Directory},
+        walker::FileSize,
+    },
+};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// most files the client can ask for
+pub const MAX_LARGEST_FILES: usize = 1000;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct LargeFile {
+    // compared first
+    size: u64,
+    path: Vec<String>,
+    allocated_size: u64,
+    previous_size: Option<u64>,
+    modified: Option<u64>,
+    accessed: Option<u64>,
+}
+
+/// The biggest files seen so far, kept as a min-heap
+/// so a new file only has to beat the smallest one.
+#[derive(Debug, Default)]
+pub struct LargestFiles {
+    heap: BinaryHeap<Reverse<LargeFile>>,
+    /// the smallest size in the full list when some were taken out,
+    /// files left out before can be up to that big
+    left_out_size: Option<u64>,
+}
+
+impl LargestFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_tree(tree: &Directory) -> Self {
+        let mut largest_files = Self::new();
+        largest_files.add_directory(tree, &mut Vec::new());
+        largest_files
+    }
+
+    fn add_directory(&mut self, dir: &Directory, path: &mut Vec<String>) {
+        for (name, file) in dir.files() {
+            if !file.hard_link {
+                self.insert_with(file.size, || LargeFile {
+                    size: file.size,
+                    path: path.iter().cloned().chain(Some(name.clone())).collect(),
+                    allocated_size: file.allocated_size,
+                    previous_size: file.previous_size,
+                    modified: file.modified,
+                    accessed: file.accessed,
+                });
+            }
+        }
+
+        for (name, child) in dir.entries() {
+            path.push(name.clone());
+            self.add_directory(child, path);
+            path.pop();
+        }
+    }
+
+    /// Returns true if the file made it into the list.
+    pub fn insert(&mut self, file: &FileSize) -> bool {
+        self.insert_with(file.size, || LargeFile {
+            size: file.size,
+            path: get_components(&file.path),
+            allocated_size: file.allocated_size,
+            previous_size: None,
+            modified: file.modified,
+            accessed: file.accessed,
+        })
+    }
+
+    fn insert_with<F: FnOnce() -> LargeFile>(&mut self, size: u64, make_file: F) -> bool {
+        if self.heap.len() >= MAX_LARGEST_FILES {
+            match self.heap.peek() {
+                Some(Reverse(smallest)) if size > smallest.size => {
+                    self.heap.pop();
+                }
+                _ => return false,
+            }
+        }
+
+        self.heap.push(Reverse(make_file()));
+        true
+    }
+
+    /// Take out every file at or below `path`, which was taken out of the tree.
+    pub fn remove_below(&mut self, path: &[String]) {
+        let smallest = match self.heap.peek() {
+            Some(Reverse(smallest)) if self.heap.len() >= MAX_LARGEST_FILES => Some(smallest.size),
+            _ => None,
+        };
+
+        let len = self.heap.len();
+        self.heap
+            .retain(|Reverse(file)| !file.path.starts_with(path));
+
+        if self.heap.len() < len {
+            self.left_out_size = self.left_out_size.max(smallest);
+        }
+    }
+
+    /// Whether a file that was left out might belong in the list now
+    /// that some were taken out, only `from_tree` can find those.
+    pub fn is_incomplete(&self) -> bool {
+        match (self.left_out_size, self.heap.peek()) {
+            (None, _) => false,
+            (Some(left_out_size), Some(Reverse(smallest)))
+                if self.heap.len() >= MAX_LARGEST_FILES =>
+            {
+                smallest.size < left_out_size
+            }
+            (Some(_), _) => true,
+        }
+    }
+
+    /// Largest first.
+    pub fn get_entries(&self, count: usize) -> Vec<Entry> {
+        let mut files: Vec<&LargeFile> = self.heap.iter().map(|Reverse(file)| file).collect();
+        files.sort_unstable_by(|a, b| b.cmp(a));
+
+        files
+            .into_iter()
+            .take(count)
+            .map(|file| Entry::File {
+                path: file.path.clone(),
+                size: file.size,
+                allocated_size: file.allocated_size,
+                previous_size: file.previous_size,
+                size_change: size_change(file.size, file.previous_size),
+                modified: file.modified,
+                accessed: file.accessed,
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_largest_files() {
+    let mut largest_files = LargestFiles::new();
+
+    for size in 0..(MAX_LARGEST_FILES as u64 + 10) {
+        largest_files.insert(&FileSize {
+            path: format!("dir/{}", size).into(),
+            size,
+            allocated_size: size,
+            ..FileSize::default()
+        });
+    }
+
+    assert!(!largest_files.insert(&FileSize {
+        path: "dir/small".into(),
+        size: 5,
+        allocated_size: 5,
+        ..FileSize::default()
+    }));
+
+    let entries = largest_files.get_entries(2);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(
+        entries[0].path(),
+        ["dir", &(MAX_LARGEST_FILES + 9).to_string()]
+    );
+    assert_eq!(
+        entries[1].path(),
+        ["dir", &(MAX_LARGEST_FILES + 8).to_string()]
+    );
+    assert_eq!(
+        largest_files.get_entries(usize::MAX).len(),
+        MAX_LARGEST_FILES
+    );
+    assert!(!largest_files.is_incomplete());
+
+    // the ones left out, like "dir/9", might belong in it now
+    largest_files.remove_below(&["dir".to_string(), "20".to_string()]);
+    assert!(largest_files.is_incomplete());
+    largest_files.insert(&FileSize {
+        path: "dir/20".into(),
+        size: 20,
+        ..FileSize::default()
+    });
+    assert!(!largest_files.is_incomplete());
+
+    largest_files.remove_below(&["dir".to_string()]);
+    assert!(largest_files.get_entries(usize::MAX).is_empty());
+    assert!(largest_files.is_incomplete());
+}
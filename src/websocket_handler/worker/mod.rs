@@ -1,29 +1,62 @@
+mod cache;
 mod dir;
+mod duplicates;
+mod extensions;
+mod hard_links;
+mod largest;
+mod ncdu;
+mod owners;
+mod roots;
+mod snapshot;
 mod tree;
+mod volumes;
 mod walker;
+mod watcher;
 
-pub use self::{dir::*, tree::*, walker::*};
-use super::api::{Entry, EventMessage};
+pub use self::{
+    cache::*, dir::*, duplicates::*, extensions::*, hard_links::*, largest::*, owners::*, roots::*,
+    snapshot::*, tree::*, volumes::*, walker::*, watcher::*,
+};
+use super::api::{Entry, ErrorKind, EventMessage};
 use futures::{
-    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    channel::mpsc::{channel, unbounded, Sender, UnboundedReceiver, UnboundedSender},
+    executor::block_on,
     future::Either,
     prelude::*,
     stream,
 };
 use log::{debug, info, warn};
-use std::{collections::HashSet, thread, time::Instant};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how often the largest files list is resent while scanning
+const LARGEST_FILES_INTERVAL: Duration = Duration::from_millis(500);
+
+/// owner totals are kept per directory so these are cheap
+const OWNERS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// extension totals are kept per directory too
+const EXTENSIONS_INTERVAL: Duration = Duration::from_millis(500);
 
 // returns "subscribed dirs"
 // which are ones in current dir
 // and those leading up to current path for breadcrumb updates
 async fn send_directory_change(
-    root_path: &[String],
     path: &[String],
-    tree: &mut Directory,
+    entries: Vec<Entry>,
+    available_space: Option<u64>,
+    tree: &Directory,
     event_sender: &mut UnboundedSender<EventMessage>,
 ) -> HashSet<Vec<String>> {
-    let (entries, available_space) = get_directory_entries(root_path, path, tree);
-
     let mut subscribed_dirs: HashSet<Vec<String>> = entries
         .iter()
         .filter_map(move |entry| {
@@ -63,131 +96,942 @@ async fn send_directory_change(
     subscribed_dirs
 }
 
+async fn send_live_directory_change(
+    roots: &Roots,
+    path: &[String],
+    tree: &Directory,
+    event_sender: &mut UnboundedSender<EventMessage>,
+) -> HashSet<Vec<String>> {
+    let (entries, available_space) = match roots.full_path(path) {
+        Some(full_path) => read_live_directory(&full_path, path, tree, event_sender),
+        // the virtual top level, nothing there but the roots
+        None => (tree.get_entries(path), None),
+    };
+
+    send_directory_change(path, entries, available_space, tree, event_sender).await
+}
+
+fn read_live_directory(
+    full_path: &Path,
+    path: &[String],
+    tree: &Directory,
+    event_sender: &mut UnboundedSender<EventMessage>,
+) -> (Vec<Entry>, Option<u64>) {
+    let available_space = match fs2::available_space(full_path) {
+        Ok(available_space) => Some(available_space),
+        Err(e) => {
+            warn!("available_space {:?}: {}", full_path, e);
+            None
+        }
+    };
+
+    let entries = match get_directory_entries(full_path.to_path_buf(), path, tree) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("couldn't read {:?}: {}", full_path, e);
+
+            if let Err(e) = event_sender.unbounded_send(EventMessage::Error {
+                path: Some(path.to_vec()),
+                message: format!("couldn't read directory: {}", e),
+                kind: ErrorKind::from(&e),
+            }) {
+                warn!("send Error: {}", e);
+            }
+
+            // whatever the scan saw, including the error
+            tree.get_entries(path)
+        }
+    };
+
+    (entries, available_space)
+}
+
+async fn send_largest_files(
+    largest_files: &LargestFiles,
+    count: usize,
+    event_sender: &mut UnboundedSender<EventMessage>,
+) {
+    if let Err(e) = event_sender
+        .send(EventMessage::LargestFiles {
+            entries: largest_files.get_entries(count),
+        })
+        .await
+    {
+        warn!("send_largest_files: {}", e);
+    }
+}
+
+async fn send_extensions(
+    tree: &Directory,
+    path: &[String],
+    event_sender: &mut UnboundedSender<EventMessage>,
+) {
+    if let Err(e) = event_sender
+        .send(EventMessage::Extensions {
+            path: path.to_vec(),
+            extensions: tree.at(path).map_or_else(Vec::new, get_extensions),
+        })
+        .await
+    {
+        warn!("send_extensions: {}", e);
+    }
+}
+
+async fn send_owners(
+    tree: &Directory,
+    path: &[String],
+    owner_names: &OwnerNames,
+    event_sender: &mut UnboundedSender<EventMessage>,
+) {
+    let (users, groups) = tree.at(path).map_or_else(Default::default, |dir| {
+        (
+            owner_names.get_users(&dir.users),
+            owner_names.get_groups(&dir.groups),
+        )
+    });
+
+    if let Err(e) = event_sender
+        .send(EventMessage::Owners {
+            path: path.to_vec(),
+            users,
+            groups,
+        })
+        .await
+    {
+        warn!("send_owners: {}", e);
+    }
+}
+
 #[derive(Debug)]
 pub enum ScannerControlMessage {
     ChangeDirectory(Vec<String>),
+    /// 0 stops the updates
+    LargestFiles(usize),
+    /// breakdown of the current directory on every change
+    Extensions(bool),
+    /// owner breakdown of the current directory on every change
+    Owners(bool),
+    /// waits for the scan to finish
+    FindDuplicates,
+    /// walk this path again, or forget it if it's gone
+    Rescan(Vec<String>),
+}
+
+/// from the threads running `walk` and the watcher
+enum WalkEvent {
+    FileType(FileType),
+    /// done walking this path, empty for the whole scan
+    Finished(Vec<String>),
+    Watch(WatchEvent),
+    /// from checking a cached tree
+    Reread(Vec<String>),
+    Validated(Vec<String>, Option<u64>),
+    Gone(Vec<String>),
+}
+
+impl From<ValidateEvent> for WalkEvent {
+    fn from(event: ValidateEvent) -> Self {
+        match event {
+            ValidateEvent::FileType(file_type) => Self::FileType(file_type),
+            ValidateEvent::Reread(path) => Self::Reread(path),
+            ValidateEvent::Validated(path, modified) => Self::Validated(path, modified),
+            ValidateEvent::Gone(path) => Self::Gone(path),
+            // same as a whole walk
+            ValidateEvent::Finished => Self::Finished(Vec::new()),
+        }
+    }
+}
+
+/// Wait to walk `path` again, unless it's inside a directory that isn't walked.
+fn queue_rescan(pending_rescans: &mut VecDeque<Vec<String>>, tree: &Directory, path: Vec<String>) {
+    if tree.is_inside_skipped(&path) {
+        debug!("not rescanning {:?} inside a skipped directory", path);
+    } else if !pending_rescans.contains(&path) {
+        pending_rescans.push_back(path);
+    }
+}
+
+/// Why a walk wouldn't have gone into `full_path` at `path` in the tree,
+/// a root is always walked.
+fn rescan_skip_reason(
+    roots: &Roots,
+    path: &[String],
+    full_path: &Path,
+    metadata: &fs::Metadata,
+    walk_options: &WalkOptions,
+) -> Option<SkipReason> {
+    let (_, root_path) = roots
+        .iter()
+        .find(|(root, _)| path.len() > root.len() && path.starts_with(root))?;
+
+    // a followed symlink to a directory is checked like one
+    let followed = if walk_options.follow_symlinks && metadata.file_type().is_symlink() {
+        fs::metadata(full_path).ok()
+    } else {
+        None
+    };
+
+    skip_reason_below(
+        root_path,
+        full_path,
+        followed.as_ref().unwrap_or(metadata),
+        walk_options,
+    )
+}
+
+/// Take `path` out of the tree and what's kept about it,
+/// with another link counted for any file that was counted there.
+/// Returns every path whose totals changed.
+fn remove_from_tree(
+    path: &[String],
+    tree: &mut Directory,
+    hard_links: &mut HardLinks,
+    largest_files: &mut LargestFiles,
+) -> Vec<Vec<String>> {
+    largest_files.remove_below(path);
+
+    let mut changed = vec![path.to_vec()];
+    for file in tree.remove_linked(path, hard_links) {
+        largest_files.insert(&file);
+        changed.push(get_components(&file.path));
+    }
+
+    changed
+}
+
+/// Start walking the first waiting path that still exists,
+/// the ones that don't are just taken out of the tree
+/// and the ones a walk wouldn't go into are marked skipped.
+/// Returns whether a walk was started and every path that changed.
+fn start_rescans(
+    roots: &Roots,
+    pending_rescans: &mut VecDeque<Vec<String>>,
+    walk_options: &WalkOptions,
+    walk_sender: &Sender<WalkEvent>,
+    tree: &mut Directory,
+    hard_links: &mut HardLinks,
+    largest_files: &mut LargestFiles,
+) -> (bool, Vec<Vec<String>>) {
+    let mut changed = Vec::new();
+
+    while let Some(mut path) = pending_rescans.pop_front() {
+        // something in a directory that's new too, that whole directory is
+        while path.len() > 1 && tree.at(&path[..path.len() - 1]).is_none() {
+            path.pop();
+        }
+
+        if let Some(full_path) = roots.full_path(&path) {
+            changed.extend(remove_from_tree(&path, tree, hard_links, largest_files));
+
+            // otherwise it's gone and removing it was all there was to do
+            if let Ok(metadata) = fs::symlink_metadata(&full_path) {
+                match rescan_skip_reason(roots, &path, &full_path, &metadata, walk_options) {
+                    // excluded files aren't in the tree at all
+                    Some(_) if !metadata.is_dir() => {}
+                    Some(reason) => {
+                        tree.update(&FileType::Skipped(path.iter().collect(), reason));
+                    }
+                    None => {
+                        // links to files outside were already counted there
+                        spawn_walk(
+                            full_path,
+                            path,
+                            walk_options.clone(),
+                            hard_links.counted(),
+                            walk_sender.clone(),
+                        );
+                        return (true, changed);
+                    }
+                }
+            }
+        } else {
+            // the virtual top level, one root at a time
+            for (root, _) in roots.iter() {
+                if !pending_rescans.iter().any(|path| path == root) {
+                    pending_rescans.push_back(root.to_vec());
+                }
+            }
+        }
+    }
+
+    (false, changed)
+}
+
+/// Walk a root, or check its cached tree that's already in `tree`.
+/// Files counted in the roots before are only `HardLink`s in this one.
+fn start_root(
+    root: Vec<String>,
+    root_path: PathBuf,
+    is_cached: bool,
+    walk_options: &WalkOptions,
+    walk_sender: &Sender<WalkEvent>,
+    tree: &Directory,
+    hard_links: &HardLinks,
+) {
+    match tree.at(&root).filter(|_| is_cached) {
+        Some(cached) => {
+            let mut walk_sender = walk_sender.clone();
+            let prefix = root.clone();
+            spawn_validate(
+                root_path,
+                cached.directory_modified(),
+                HardLinks::from_tree(cached),
+                hard_links.outside(&root),
+                walk_options.clone(),
+                move |mut event| {
+                    event.add_prefix(&prefix);
+                    block_on(walk_sender.send(event.into())).is_ok()
+                },
+            );
+        }
+
+        None => {
+            spawn_walk(
+                root_path,
+                root,
+                walk_options.clone(),
+                hard_links.counted(),
+                walk_sender.clone(),
+            );
+        }
+    }
+}
+
+/// how many walked entries can wait for the scanner
+const WALK_BUFFER: usize = 1024;
+
+/// Walk `full_path` on its own thread,
+/// with paths in the events under `path` in the tree.
+/// Files in `counted` are already counted elsewhere in it.
+fn spawn_walk(
+    full_path: PathBuf,
+    path: Vec<String>,
+    walk_options: WalkOptions,
+    counted: HashSet<(u64, u64)>,
+    mut walk_sender: Sender<WalkEvent>,
+) {
+    let prefix: PathBuf = path.iter().collect();
+
+    thread::spawn(move || {
+        for mut file_type in walk_part(full_path, &walk_options, counted) {
+            if !prefix.as_os_str().is_empty() {
+                file_type.add_prefix(&prefix);
+            }
+
+            // the scanner is gone
+            if block_on(walk_sender.send(WalkEvent::FileType(file_type))).is_err() {
+                return;
+            }
+        }
+
+        if let Err(e) = block_on(walk_sender.send(WalkEvent::Finished(path))) {
+            debug!("walk_sender Finished: {}", e);
+        }
+    });
+}
+
+/// Which websocket a message to the scanner is from.
+type ClientId = usize;
+
+/// to the shared scanner from each websocket
+#[derive(Debug)]
+enum ClientMessage {
+    Connected(UnboundedSender<EventMessage>),
+    Control(ScannerControlMessage),
+    Disconnected,
+}
+
+/// One scan shared by every websocket, each with its own current directory.
+/// Scanning stops once every clone is dropped.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    client_sender: UnboundedSender<(ClientId, ClientMessage)>,
+    next_client_id: Arc<AtomicUsize>,
+}
+
+impl Scanner {
+    /// Events for a new websocket, starting with its first `ChangeDirectory`.
+    /// Dropping the returned sender disconnects it.
+    pub fn connect(
+        &self,
+    ) -> (
+        UnboundedSender<ScannerControlMessage>,
+        UnboundedReceiver<EventMessage>,
+    ) {
+        let client_id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        let (event_sender, event_receiver) = unbounded();
+        let (control_sender, mut control_receiver) = unbounded();
+
+        if let Err(e) = self
+            .client_sender
+            .unbounded_send((client_id, ClientMessage::Connected(event_sender)))
+        {
+            warn!("scanner Connected: {}", e);
+        }
+
+        let mut client_sender = self.client_sender.clone();
+        tokio::spawn(async move {
+            while let Some(control_message) = control_receiver.next().await {
+                if client_sender
+                    .send((client_id, ClientMessage::Control(control_message)))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+
+            if let Err(e) = client_sender
+                .send((client_id, ClientMessage::Disconnected))
+                .await
+            {
+                debug!("scanner Disconnected: {}", e);
+            }
+        });
+
+        (control_sender, event_receiver)
+    }
+}
+
+/// What one websocket is looking at and subscribed to.
+#[allow(clippy::struct_excessive_bools)]
+struct Client {
+    event_sender: UnboundedSender<EventMessage>,
+    current_dir: Vec<String>,
+    /// from `send_directory_change`
+    subscribed_dirs: HashSet<Vec<String>>,
+    /// 0 if it doesn't want them
+    largest_files_count: usize,
+    extensions_subscribed: bool,
+    extensions_changed: bool,
+    extensions_sent_time: Instant,
+    owners_subscribed: bool,
+    owners_changed: bool,
+    owners_sent_time: Instant,
+    /// searched once the scan finishes
+    duplicates_requested: bool,
+}
+
+impl Client {
+    fn new(event_sender: UnboundedSender<EventMessage>) -> Self {
+        let now = Instant::now();
+
+        Self {
+            event_sender,
+            current_dir: Vec::new(),
+            subscribed_dirs: HashSet::new(),
+            largest_files_count: 0,
+            extensions_subscribed: false,
+            extensions_changed: false,
+            extensions_sent_time: now,
+            owners_subscribed: false,
+            owners_changed: false,
+            owners_sent_time: now,
+            duplicates_requested: false,
+        }
+    }
+
+    async fn change_dir(
+        &mut self,
+        roots: &Roots,
+        path: Vec<String>,
+        tree: &Directory,
+        owner_names: &OwnerNames,
+    ) {
+        self.current_dir = path;
+        self.send_directory(roots, tree).await;
+
+        if self.extensions_subscribed {
+            self.send_extensions(tree).await;
+        }
+        if self.owners_subscribed {
+            self.send_owners(tree, owner_names).await;
+        }
+    }
+
+    /// Whether a change at `path` shows in the current directory,
+    /// it's inside or it's the current directory or one above it.
+    fn is_affected_by(&self, path: &[String]) -> bool {
+        path.starts_with(&self.current_dir) || self.current_dir.starts_with(path)
+    }
+
+    /// The whole listing again, for when more than sizes changed.
+    async fn send_directory(&mut self, roots: &Roots, tree: &Directory) {
+        self.subscribed_dirs =
+            send_live_directory_change(roots, &self.current_dir, tree, &mut self.event_sender)
+                .await;
+    }
+
+    async fn send_largest_files(&mut self, largest_files: &LargestFiles) {
+        if self.largest_files_count > 0 {
+            send_largest_files(
+                largest_files,
+                self.largest_files_count,
+                &mut self.event_sender,
+            )
+            .await;
+        }
+    }
+
+    async fn send_extensions(&mut self, tree: &Directory) {
+        send_extensions(tree, &self.current_dir, &mut self.event_sender).await;
+        self.extensions_changed = false;
+        self.extensions_sent_time = Instant::now();
+    }
+
+    async fn send_owners(&mut self, tree: &Directory, owner_names: &OwnerNames) {
+        send_owners(tree, &self.current_dir, owner_names, &mut self.event_sender).await;
+        self.owners_changed = false;
+        self.owners_sent_time = Instant::now();
+    }
+
+    /// Extensions and owners if they changed,
+    /// `throttle` waits out their intervals while walking.
+    async fn send_changed(&mut self, tree: &Directory, owner_names: &OwnerNames, throttle: bool) {
+        if self.extensions_changed
+            && self.extensions_subscribed
+            && (!throttle || self.extensions_sent_time.elapsed() >= EXTENSIONS_INTERVAL)
+        {
+            self.send_extensions(tree).await;
+        }
+
+        if self.owners_changed
+            && self.owners_subscribed
+            && (!throttle || self.owners_sent_time.elapsed() >= OWNERS_INTERVAL)
+        {
+            self.send_owners(tree, owner_names).await;
+        }
+    }
+
+    /// `SizeUpdate` for the shown directory `path` is in, if there is one.
+    async fn send_size_update(&mut self, path: &[String], tree: &Directory) {
+        let shown = if path.len() > self.current_dir.len() && path.starts_with(&self.current_dir) {
+            // in the current directory,
+            // this ignores higher directory changes
+            self.extensions_changed = true;
+            self.owners_changed = true;
+
+            &path[..=self.current_dir.len()]
+        } else {
+            path
+        };
+
+        if self.subscribed_dirs.contains(shown) {
+            if let Err(e) = self
+                .event_sender
+                .send(EventMessage::SizeUpdate {
+                    entry: tree.get_entry_directory(shown.to_vec()),
+                })
+                .await
+            {
+                warn!("scanner to event_sender: {}", e);
+            }
+        }
+    }
 }
 
+/// Start scanning every root on its own thread
+/// for any number of websockets to `Scanner::connect` to.
 #[allow(clippy::cognitive_complexity)]
 #[allow(clippy::too_many_lines)]
-pub async fn spawn_scanner_stream(
-    root_path: Vec<String>,
-    mut control_receiver: UnboundedReceiver<ScannerControlMessage>,
-) -> UnboundedReceiver<EventMessage> {
-    let (mut event_sender, event_receiver) = unbounded();
+pub fn spawn_scanner(
+    roots: Roots,
+    walk_options: WalkOptions,
+    previous: Option<Arc<Snapshot>>,
+    watch: bool,
+    cache: bool,
+) -> Scanner {
+    let (client_sender, client_receiver) = unbounded();
 
     // Use a separate thread for this future because
     // iterator-streams block the tokio threadpool,
-    // which causes the control_receiver to never be heard
+    // which causes the client_receiver to never be heard
 
     thread::spawn(move || {
         futures::executor::block_on(async move {
+            let start_time = Instant::now();
+
             let mut tree = Directory::new();
+            if roots.is_virtual() {
+                // finished along with the last root
+                tree.update(&FileType::Dir(PathBuf::new(), DirStatus::Started(None)));
+            }
 
-            let start_time = Instant::now();
+            let (walk_sender, walk_receiver) = channel(WALK_BUFFER);
 
-            let mut current_dir;
-            let mut subscribed_dirs;
+            // shown right away, each directory is checked before it says Finished
+            let mut from_cache = false;
+            let mut pending_roots = VecDeque::new();
+            for (root, root_path) in roots.iter() {
+                let cached = if cache {
+                    load_cache(root_path, &walk_options)
+                } else {
+                    None
+                };
 
-            // wait for default current directory
-            match control_receiver.next().await.unwrap() {
-                ScannerControlMessage::ChangeDirectory(path) => {
-                    subscribed_dirs =
-                        send_directory_change(&root_path, &path, &mut tree, &mut event_sender)
-                            .await;
-                    current_dir = path;
+                let is_cached = cached.is_some();
+                if let Some(mut cached) = cached {
+                    from_cache = true;
+                    cached.set_all_updating();
+                    if let Some(name) = root.first() {
+                        tree.insert(name.clone(), cached);
+                    } else {
+                        tree = cached;
+                    }
                 }
+
+                pending_roots.push_back((root.to_vec(), root_path.to_path_buf(), is_cached));
+            }
+            // including any cached files
+            let mut hard_links = HardLinks::from_tree(&tree);
+
+            // one root after another, a file linked from several
+            // is counted in the first and only a HardLink in the rest
+            let mut walking = 0;
+            if let Some((root, root_path, is_cached)) = pending_roots.pop_front() {
+                start_root(
+                    root,
+                    root_path,
+                    is_cached,
+                    &walk_options,
+                    &walk_sender,
+                    &tree,
+                    &hard_links,
+                );
+                walking += 1;
             }
 
-            let file_size_stream = stream::iter(walk(root_path.iter().collect()));
+            // rescans wait for these, and then go one at a time
+            // so they don't count the same files
+            let mut pending_rescans = VecDeque::new();
+            let mut largest_files = LargestFiles::from_tree(&tree);
+            let mut largest_files_changed = false;
+            let mut largest_files_sent_time = start_time;
+
+            let owner_names = OwnerNames::default();
+            let mut clients: HashMap<ClientId, Client> = HashMap::new();
+
+            let mut scan_finished = false;
+            // once the scan is finished, if asked to
+            let mut dir_watcher: Option<DirWatcher> = None;
 
             let mut either_stream = stream::select(
-                control_receiver
+                client_receiver
                     .map(Some)
                     .chain(stream::once(future::ready(None)))
                     .map(Either::Left),
-                file_size_stream
-                    .map(Some)
-                    .chain(stream::once(future::ready(None)))
-                    .map(Either::Right),
+                // never ends since we keep a walk_sender
+                walk_receiver.map(Either::Right),
             );
 
             while let Some(either) = either_stream.next().await {
                 match either {
                     Either::Left(None) => {
-                        debug!("control_receiver hangup");
+                        debug!("client_receiver hangup");
+
+                        // with whatever changed since
+                        if cache && scan_finished {
+                            save_caches(&roots, &walk_options, &mut tree);
+                        }
                         break;
                     }
 
-                    Either::Left(Some(ScannerControlMessage::ChangeDirectory(path))) => {
-                        debug!("control_receiver ChangeDirectory {:?}", path);
+                    Either::Left(Some((client_id, ClientMessage::Connected(event_sender)))) => {
+                        debug!("client {} connected", client_id);
 
-                        subscribed_dirs =
-                            send_directory_change(&root_path, &path, &mut tree, &mut event_sender)
-                                .await;
-                        current_dir = path;
+                        clients.insert(client_id, Client::new(event_sender));
                     }
 
-                    Either::Right(None) => {
-                        let end_time = Instant::now();
-                        info!("scanner done! {:?}", end_time - start_time);
+                    Either::Left(Some((client_id, ClientMessage::Disconnected))) => {
+                        debug!("client {} disconnected", client_id);
+
+                        clients.remove(&client_id);
+
+                        // the server might stop with the last one
+                        if clients.is_empty() && cache && scan_finished {
+                            save_caches(&roots, &walk_options, &mut tree);
+
+                            // for anyone connecting before it does
+                            if let Some(previous) = &previous {
+                                tree.compare(&previous.tree);
+                            }
+                        }
                     }
 
-                    Either::Right(Some(file_type)) => {
-                        tree.update(&file_type);
+                    Either::Left(Some((client_id, ClientMessage::Control(control_message)))) => {
+                        debug!("client {} {:?}", client_id, control_message);
+
+                        if let Some(client) = clients.get_mut(&client_id) {
+                            match control_message {
+                                ScannerControlMessage::ChangeDirectory(path) => {
+                                    client.change_dir(&roots, path, &tree, &owner_names).await;
+                                }
+
+                                ScannerControlMessage::LargestFiles(count) => {
+                                    client.largest_files_count = count.min(MAX_LARGEST_FILES);
+                                    client.send_largest_files(&largest_files).await;
+                                }
 
-                        match file_type {
-                            FileType::File(FileSize(path, _)) => {
-                                // send update for total size of shown directories
-
-                                // [ (src), websocket_handler, worker, mod.rs ]
-                                let components = get_components(&path);
-
-                                // current_dir = src
-                                // true
-                                if components.starts_with(&current_dir) {
-                                    // file is in current directory
-                                    // This ignores higher directory changes
-
-                                    let components = components[..=current_dir.len()].to_vec();
-                                    // current_dir.len() = 1
-                                    // [ ( (src), websocket_handler ), worker, mod.rs ]
-
-                                    // [ websocket_handler, src ]
-                                    if subscribed_dirs.contains(&components) {
-                                        // File is in a directory in the current dir.
-                                        // Update that directory's size.
-
-                                        if let Err(e) = event_sender
-                                            .send(EventMessage::SizeUpdate {
-                                                entry: tree.get_entry_directory(components),
-                                            })
-                                            .await
-                                        {
-                                            warn!("scanner to event_sender: {}", e);
-                                        }
+                                ScannerControlMessage::Extensions(subscribe) => {
+                                    client.extensions_subscribed = subscribe;
+                                    if subscribe {
+                                        client.send_extensions(&tree).await;
                                     }
                                 }
-                            }
 
-                            FileType::Dir(path, _) => {
-                                let components = get_components(&path);
-                                if subscribed_dirs.contains(&components) {
-                                    // Dir is in our current dir
-                                    // We don't care about recursion
-
-                                    if let Err(e) = event_sender
-                                        .send(EventMessage::SizeUpdate {
-                                            entry: tree.get_entry_directory(components),
-                                        })
-                                        .await
-                                    {
-                                        warn!("scanner to event_sender: {}", e);
+                                ScannerControlMessage::Owners(subscribe) => {
+                                    client.owners_subscribed = subscribe;
+                                    if subscribe {
+                                        client.send_owners(&tree, &owner_names).await;
                                     }
                                 }
+
+                                ScannerControlMessage::FindDuplicates => {
+                                    if scan_finished {
+                                        spawn_duplicate_search(
+                                            roots.clone(),
+                                            &tree,
+                                            client.event_sender.clone(),
+                                        );
+                                    } else {
+                                        client.duplicates_requested = true;
+                                    }
+                                }
+
+                                ScannerControlMessage::Rescan(path) => {
+                                    queue_rescan(&mut pending_rescans, &tree, path);
+                                }
                             }
                         }
                     }
+
+                    Either::Right(WalkEvent::Watch(WatchEvent::Changed(paths))) => {
+                        debug!("watcher Changed {:?}", paths);
+
+                        for path in paths {
+                            queue_rescan(&mut pending_rescans, &tree, get_components(path));
+                        }
+                    }
+
+                    Either::Right(WalkEvent::Reread(path)) => {
+                        tree.remove_files(&path, &mut hard_links);
+                        for client in clients.values_mut() {
+                            client.send_size_update(&path, &tree).await;
+                        }
+                    }
+
+                    Either::Right(WalkEvent::Validated(path, modified)) => {
+                        tree.set_validated(&path, modified);
+                        for client in clients.values_mut() {
+                            client.send_size_update(&path, &tree).await;
+                        }
+                    }
+
+                    Either::Right(WalkEvent::Gone(path)) => {
+                        // the largest files are found again once everything is checked
+                        tree.remove_linked(&path, &mut hard_links);
+                        for client in clients.values_mut() {
+                            client.send_size_update(&path, &tree).await;
+                        }
+                    }
+
+                    Either::Right(WalkEvent::Watch(WatchEvent::Error(message))) => {
+                        warn!("watcher: {}", message);
+
+                        for client in clients.values_mut() {
+                            if let Err(e) = client
+                                .event_sender
+                                .send(EventMessage::Error {
+                                    path: None,
+                                    message: message.clone(),
+                                    kind: ErrorKind::Other,
+                                })
+                                .await
+                            {
+                                warn!("scanner to event_sender: {}", e);
+                            }
+                        }
+                    }
+
+                    Either::Right(WalkEvent::Finished(path)) if scan_finished => {
+                        info!("rescan of {:?} done", path);
+                        walking -= 1;
+
+                        if let Some(previous) = &previous {
+                            tree.compare_at(&path, &previous.tree);
+                        }
+
+                        // a smaller file outside might have moved up
+                        if largest_files.is_incomplete() {
+                            largest_files = LargestFiles::from_tree(&tree);
+                            largest_files_changed = true;
+                        }
+
+                        for client in clients.values_mut() {
+                            if largest_files_changed {
+                                client.send_largest_files(&largest_files).await;
+                            }
+
+                            if client.is_affected_by(&path) {
+                                client.extensions_changed = true;
+                                client.owners_changed = true;
+                                client.send_changed(&tree, &owner_names, false).await;
+                                client.send_directory(&roots, &tree).await;
+                            }
+                        }
+                        largest_files_changed = false;
+                    }
+
+                    Either::Right(WalkEvent::Finished(path)) if !pending_roots.is_empty() => {
+                        info!("{:?} done, {} roots left", path, pending_roots.len());
+
+                        if let Some((root, root_path, is_cached)) = pending_roots.pop_front() {
+                            start_root(
+                                root,
+                                root_path,
+                                is_cached,
+                                &walk_options,
+                                &walk_sender,
+                                &tree,
+                                &hard_links,
+                            );
+                        }
+                    }
+
+                    Either::Right(WalkEvent::Finished(_)) => {
+                        let end_time = Instant::now();
+                        info!("scanner done! {:?}", end_time - start_time);
+
+                        scan_finished = true;
+                        walking = 0;
+
+                        if roots.is_virtual() {
+                            tree.update(&FileType::Dir(PathBuf::new(), DirStatus::Finished));
+                        }
+
+                        if from_cache {
+                            // without files that are gone
+                            largest_files = LargestFiles::from_tree(&tree);
+                            largest_files_changed = true;
+                        }
+                        if cache {
+                            save_caches(&roots, &walk_options, &mut tree);
+                        }
+
+                        // sizes are only comparable once everything is counted
+                        if let Some(previous) = &previous {
+                            tree.compare(&previous.tree);
+                        }
+
+                        for client in clients.values_mut() {
+                            if client.duplicates_requested {
+                                spawn_duplicate_search(
+                                    roots.clone(),
+                                    &tree,
+                                    client.event_sender.clone(),
+                                );
+                                client.duplicates_requested = false;
+                            }
+
+                            if largest_files_changed {
+                                client.send_largest_files(&largest_files).await;
+                            }
+                            client.send_changed(&tree, &owner_names, false).await;
+
+                            if previous.is_some() {
+                                client.send_directory(&roots, &tree).await;
+                            }
+                        }
+                        largest_files_changed = false;
+
+                        if watch {
+                            // ones that couldn't be listed can't be watched either
+                            let directories = tree
+                                .directory_modified()
+                                .into_iter()
+                                .map(|(path, _)| path)
+                                .filter(|path| {
+                                    tree.at(path).and_then(|dir| dir.error.as_ref()).is_none()
+                                })
+                                .collect();
+                            let mut walk_sender = walk_sender.clone();
+                            dir_watcher = spawn_watcher(&roots, directories, move |event| {
+                                block_on(walk_sender.send(WalkEvent::Watch(event))).is_ok()
+                            });
+                        }
+                    }
+
+                    Either::Right(WalkEvent::FileType(file_type)) => {
+                        tree.update(&file_type);
+                        hard_links.update(&file_type);
+
+                        // directories new since watching started
+                        if let (Some(dir_watcher), FileType::Dir(path, DirStatus::Started(_))) =
+                            (&dir_watcher, &file_type)
+                        {
+                            dir_watcher.watch(&get_components(path));
+                        }
+
+                        if let FileType::File(file) = &file_type {
+                            if largest_files.insert(file) {
+                                largest_files_changed = true;
+                            }
+                        }
+
+                        if largest_files_changed
+                            && largest_files_sent_time.elapsed() >= LARGEST_FILES_INTERVAL
+                        {
+                            for client in clients.values_mut() {
+                                client.send_largest_files(&largest_files).await;
+                            }
+                            largest_files_changed = false;
+                            largest_files_sent_time = Instant::now();
+                        }
+
+                        // send update for total size of shown directories
+                        let path = match &file_type {
+                            FileType::File(FileSize { path, .. })
+                            | FileType::HardLink(FileSize { path, .. })
+                            | FileType::Error(path, _)
+                            | FileType::Dir(path, _)
+                            | FileType::Skipped(path, _) => Some(get_components(path)),
+
+                            // the link's own size isn't in any total
+                            FileType::Symlink(..) | FileType::Special(..) => None,
+                        };
+
+                        for client in clients.values_mut() {
+                            if let Some(path) = &path {
+                                client.send_size_update(path, &tree).await;
+                            }
+                            client.send_changed(&tree, &owner_names, true).await;
+                        }
+                    }
+                }
+
+                // asked for by a client or the watcher, or while scanning
+                if walking == 0 && !pending_rescans.is_empty() {
+                    let (started, changed) = start_rescans(
+                        &roots,
+                        &mut pending_rescans,
+                        &walk_options,
+                        &walk_sender,
+                        &mut tree,
+                        &mut hard_links,
+                        &mut largest_files,
+                    );
+                    if started {
+                        walking += 1;
+                    }
+                    largest_files_changed = true;
+
+                    for client in clients.values_mut() {
+                        if changed.iter().any(|path| client.is_affected_by(path)) {
+                            client.extensions_changed = true;
+                            client.owners_changed = true;
+                            client.send_directory(&roots, &tree).await;
+                        }
+                    }
                 }
             } // while either_stream
 
@@ -195,5 +1039,90 @@ pub async fn spawn_scanner_stream(
         });
     });
 
+    Scanner {
+        client_sender,
+        next_client_id: Arc::new(AtomicUsize::new(0)),
+    }
+}
+
+/// Answer directory changes from an imported scan, nothing is read from disk.
+pub fn spawn_snapshot_stream(
+    snapshot: Arc<Snapshot>,
+    mut control_receiver: UnboundedReceiver<ScannerControlMessage>,
+) -> UnboundedReceiver<EventMessage> {
+    let (mut event_sender, event_receiver) = unbounded();
+
+    tokio::spawn(async move {
+        // only built if asked for
+        let mut largest_files = None;
+
+        let mut current_dir = Vec::new();
+        let mut extensions_subscribed = false;
+
+        let owner_names = OwnerNames::default();
+        let mut owners_subscribed = false;
+
+        while let Some(control_message) = control_receiver.next().await {
+            match control_message {
+                ScannerControlMessage::ChangeDirectory(path) => {
+                    let entries = snapshot.tree.get_entries(&path);
+                    send_directory_change(&path, entries, None, &snapshot.tree, &mut event_sender)
+                        .await;
+                    if extensions_subscribed {
+                        send_extensions(&snapshot.tree, &path, &mut event_sender).await;
+                    }
+                    if owners_subscribed {
+                        send_owners(&snapshot.tree, &path, &owner_names, &mut event_sender).await;
+                    }
+                    current_dir = path;
+                }
+
+                ScannerControlMessage::Extensions(subscribe) => {
+                    extensions_subscribed = subscribe;
+                    if extensions_subscribed {
+                        send_extensions(&snapshot.tree, &current_dir, &mut event_sender).await;
+                    }
+                }
+
+                ScannerControlMessage::Owners(subscribe) => {
+                    owners_subscribed = subscribe;
+                    if owners_subscribed {
+                        send_owners(
+                            &snapshot.tree,
+                            &current_dir,
+                            &owner_names,
+                            &mut event_sender,
+                        )
+                        .await;
+                    }
+                }
+
+                ScannerControlMessage::FindDuplicates => {
+                    // the files might not even be on this computer
+                    warn!("can't search an imported scan for duplicates");
+                }
+
+                ScannerControlMessage::Rescan(path) => {
+                    warn!("can't rescan {:?} in an imported scan", path);
+                }
+
+                ScannerControlMessage::LargestFiles(0) => {}
+
+                ScannerControlMessage::LargestFiles(count) => {
+                    let largest_files = largest_files
+                        .get_or_insert_with(|| LargestFiles::from_tree(&snapshot.tree));
+                    send_largest_files(
+                        largest_files,
+                        count.min(MAX_LARGEST_FILES),
+                        &mut event_sender,
+                    )
+                    .await;
+                }
+            }
+        }
+
+        debug!("snapshot control_receiver completed");
+    });
+
     event_receiver
 }
@@ -0,0 +1,429 @@
+//! ncdu's json export format, see <https://dev.yorhel.nl/ncdu/jsonfmt>
+//!
+//! `[1, 0, {metadata}, [{root info}, {file}, [{dir info}, ...], ...]]`
+
+use super::{read_json, DirStatus, Directory, FileInfo, FileSize, FileType, SkipReason, Snapshot};
+use crate::websocket_handler::api::SpecialKind;
+use failure::{bail, format_err, Error};
+use serde::{
+    ser::{SerializeSeq, Serializer},
+    Serialize,
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    io::{Read, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAJOR_VERSION: u64 = 1;
+const MINOR_VERSION: u64 = 0;
+
+#[derive(Serialize)]
+struct Metadata {
+    progname: &'static str,
+    progver: &'static str,
+    timestamp: u64,
+}
+
+#[derive(Serialize)]
+struct Info<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asize: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dsize: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    excluded: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_error: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notreg: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hlnkc: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ino: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dev: Option<u64>,
+}
+
+impl<'a> Info<'a> {
+    fn name(name: &'a str) -> Self {
+        Self {
+            name,
+            asize: None,
+            dsize: None,
+            mtime: None,
+            excluded: None,
+            read_error: None,
+            notreg: None,
+            hlnkc: None,
+            ino: None,
+            dev: None,
+        }
+    }
+
+    fn error(name: &'a str) -> Self {
+        Self {
+            read_error: Some(true),
+            ..Self::name(name)
+        }
+    }
+
+    fn file(name: &'a str, file: &FileInfo) -> Self {
+        let info = Self {
+            asize: Some(file.size),
+            dsize: Some(file.allocated_size),
+            mtime: file.modified,
+            ..Self::name(name)
+        };
+
+        match file.inode.filter(|_| file.multiple_links) {
+            // ncdu counts each inode once, like we do
+            Some((device, inode)) => Self {
+                hlnkc: Some(true),
+                ino: Some(inode),
+                dev: Some(device),
+                ..info
+            },
+
+            // reached again through a symlink, or from an export older than
+            // inodes were kept, written empty to keep ncdu's totals the same as ours
+            None if file.hard_link => Self {
+                asize: Some(0),
+                dsize: Some(0),
+                ..info
+            },
+
+            None => info,
+        }
+    }
+
+    // symlinks and special files, without a size
+    // since a link's own isn't in our totals either
+    fn not_regular(name: &'a str) -> Self {
+        Self {
+            notreg: Some(true),
+            ..Self::name(name)
+        }
+    }
+
+    fn skipped(name: &'a str, reason: SkipReason) -> Self {
+        Self {
+            excluded: Some(match reason {
+                SkipReason::MountPoint => "otherfs",
+                SkipReason::Excluded => "pattern",
+            }),
+            ..Self::name(name)
+        }
+    }
+}
+
+struct NcduDirectory<'a> {
+    name: &'a str,
+    dir: &'a Directory,
+}
+
+impl Serialize for NcduDirectory<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        if self.dir.error.is_some() {
+            seq.serialize_element(&Info::error(self.name))?;
+        } else {
+            seq.serialize_element(&Info::name(self.name))?;
+        }
+
+        for (name, file) in self.dir.files() {
+            seq.serialize_element(&Info::file(name, file))?;
+        }
+
+        for name in self
+            .dir
+            .symlinks()
+            .map(|(name, _)| name)
+            .chain(self.dir.specials().map(|(name, _)| name))
+        {
+            seq.serialize_element(&Info::not_regular(name))?;
+        }
+
+        for (name, _message) in self.dir.errors() {
+            seq.serialize_element(&Info::error(name))?;
+        }
+
+        for (name, child) in self.dir.entries() {
+            if let Some(reason) = child.skipped {
+                seq.serialize_element(&Info::skipped(name, reason))?;
+            } else {
+                seq.serialize_element(&NcduDirectory { name, dir: child })?;
+            }
+        }
+
+        seq.end()
+    }
+}
+
+impl Snapshot {
+    pub fn write_ncdu<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+
+        let root_name = self.root_path.to_string_lossy();
+
+        serde_json::to_writer(
+            writer,
+            &(
+                MAJOR_VERSION,
+                MINOR_VERSION,
+                Metadata {
+                    progname: env!("CARGO_PKG_NAME"),
+                    progver: env!("CARGO_PKG_VERSION"),
+                    timestamp,
+                },
+                NcduDirectory {
+                    name: &root_name,
+                    dir: &self.tree,
+                },
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn read_ncdu<R: Read>(reader: R) -> Result<Self, Error> {
+        let value: Value = read_json(reader)?;
+
+        let items = value
+            .as_array()
+            .ok_or_else(|| format_err!("ncdu export isn't an array"))?;
+
+        let major_version = items.first().and_then(Value::as_u64);
+        if major_version != Some(MAJOR_VERSION) {
+            bail!("unsupported ncdu export version {:?}", major_version);
+        }
+
+        let root = items
+            .get(3)
+            .and_then(Value::as_array)
+            .ok_or_else(|| format_err!("ncdu export has no root directory"))?;
+
+        let root_path = PathBuf::from(info_name(root)?);
+
+        // build the tree with the same events the walker would send
+        let mut tree = Directory::new();
+        let mut seen_hard_links = HashSet::new();
+        read_directory(root, PathBuf::new(), 0, &mut tree, &mut seen_hard_links)?;
+
+        Ok(Self { root_path, tree })
+    }
+}
+
+fn info_name(dir: &[Value]) -> Result<&str, Error> {
+    dir.first()
+        .and_then(Value::as_object)
+        .and_then(object_name)
+        .ok_or_else(|| format_err!("ncdu directory without a name"))
+}
+
+fn object_name(info: &Map<String, Value>) -> Option<&str> {
+    info.get("name").and_then(Value::as_str)
+}
+
+// `dev` is only there when it's different from the parent's
+fn read_directory(
+    dir: &[Value],
+    path: PathBuf,
+    dev: u64,
+    tree: &mut Directory,
+    seen_hard_links: &mut HashSet<(u64, u64)>,
+) -> Result<(), Error> {
+    let info = dir.first().and_then(Value::as_object);
+    let modified = info
+        .and_then(|info| info.get("mtime"))
+        .and_then(Value::as_u64);
+    let dev = info
+        .and_then(|info| info.get("dev"))
+        .and_then(Value::as_u64)
+        .unwrap_or(dev);
+    tree.update(&FileType::Dir(path.clone(), DirStatus::Started(modified)));
+
+    if let Some(info) = info {
+        if has_read_error(info) {
+            tree.update(&FileType::Error(path.clone(), READ_ERROR.to_string()));
+        }
+    }
+
+    for item in dir.iter().skip(1) {
+        match item {
+            Value::Array(child) => {
+                let child_path = path.join(info_name(child)?);
+                read_directory(child, child_path, dev, tree, seen_hard_links)?;
+            }
+
+            Value::Object(info) => {
+                let name =
+                    object_name(info).ok_or_else(|| format_err!("ncdu entry without a name"))?;
+                tree.update(&read_item(info, path.join(name), dev, seen_hard_links));
+            }
+
+            _ => bail!("unexpected ncdu entry {}", item),
+        }
+    }
+
+    tree.update(&FileType::Dir(path, DirStatus::Finished));
+
+    Ok(())
+}
+
+// ncdu doesn't say why
+const READ_ERROR: &str = "couldn't be read when exported by ncdu";
+
+fn has_read_error(info: &Map<String, Value>) -> bool {
+    info.get("read_error").and_then(Value::as_bool) == Some(true)
+}
+
+fn read_item(
+    info: &Map<String, Value>,
+    path: PathBuf,
+    dev: u64,
+    seen_hard_links: &mut HashSet<(u64, u64)>,
+) -> FileType {
+    let get_u64 = |key| info.get(key).and_then(Value::as_u64);
+
+    match info.get("excluded").and_then(Value::as_str) {
+        Some("otherfs" | "kernfs") => {
+            return FileType::Skipped(path, SkipReason::MountPoint);
+        }
+        Some(_) => return FileType::Skipped(path, SkipReason::Excluded),
+        None => {}
+    }
+
+    // ncdu also sets this on files it could only partly read, those still have sizes
+    if has_read_error(info) && info.get("asize").is_none() {
+        return FileType::Error(path, READ_ERROR.to_string());
+    }
+
+    // without ino there's nothing to tell the links apart, so each is counted
+    // ncdu doesn't say whether it's a symlink, fifo or device, and has no target
+    if info.get("notreg").and_then(Value::as_bool) == Some(true) {
+        return FileType::Special(path, SpecialKind::Other);
+    }
+
+    let hard_link = info.get("hlnkc").and_then(Value::as_bool) == Some(true);
+    let inode = get_u64("ino")
+        .filter(|_| hard_link)
+        .map(|ino| (get_u64("dev").unwrap_or(dev), ino));
+
+    let file = FileSize {
+        path,
+        size: get_u64("asize").unwrap_or(0),
+        allocated_size: get_u64("dsize").unwrap_or(0),
+        // only in ncdu's extended mode
+        modified: get_u64("mtime"),
+        accessed: None,
+        uid: get_u64("uid").and_then(|uid| u32::try_from(uid).ok()),
+        gid: get_u64("gid").and_then(|gid| u32::try_from(gid).ok()),
+        inode,
+        multiple_links: inode.is_some(),
+    };
+
+    match inode {
+        Some(id) if !seen_hard_links.insert(id) => FileType::HardLink(file),
+        _ => FileType::File(file),
+    }
+}
+
+#[test]
+fn test_read_ncdu() {
+    use std::path::Path;
+
+    let export = br#"[1, 2, {"progname": "ncdu", "progver": "1.15", "timestamp": 0},
+        [{"name": "/data", "asize": 4096, "dsize": 4096},
+            {"name": "a", "asize": 100, "dsize": 4096, "mtime": 1000},
+            {"name": "b", "asize": 100, "dsize": 4096, "hlnkc": true, "ino": 7},
+            [{"name": "sub"},
+                {"name": "c", "asize": 50, "dsize": 0},
+                {"name": "e", "asize": 10, "dsize": 0, "hlnkc": true},
+                {"name": "f", "asize": 10, "dsize": 0, "hlnkc": true},
+                {"name": "d", "asize": 100, "dsize": 4096, "hlnkc": true, "ino": 7}
+            ],
+            {"name": "link", "notreg": true},
+            {"name": "proc", "excluded": "otherfs"},
+            {"name": "gone", "read_error": true},
+            [{"name": "locked", "read_error": true}]
+        ]
+    ]"#;
+
+    let mut snapshot = Snapshot::read_ncdu(&export[..]).unwrap();
+    assert_eq!(snapshot.root_path, Path::new("/data"));
+
+    let tree = &snapshot.tree;
+    assert_eq!(tree.total_size, 270);
+    assert_eq!(tree.total_allocated_size, 8192);
+    assert_eq!(tree.hard_links, 1);
+    assert_eq!(tree.last_modified, Some(1000));
+    assert_eq!(tree.unreadable_count, 2);
+
+    let sub = tree.at(&["sub".to_string()]).unwrap();
+    assert_eq!(sub.total_size, 70);
+    assert_eq!(sub.hard_links_size, 100);
+
+    let proc = tree.at(&["proc".to_string()]).unwrap();
+    assert!(matches!(proc.skipped, Some(SkipReason::MountPoint)));
+
+    let specials = |tree: &Directory| {
+        let mut names: Vec<_> = tree
+            .specials()
+            .map(|(name, kind)| (name.clone(), *kind))
+            .collect();
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        names
+    };
+    assert_eq!(specials(tree), [("link".to_string(), SpecialKind::Other)]);
+
+    // and back again, with the kinds our own walk finds
+    let symlink = FileSize {
+        path: PathBuf::from("to-a"),
+        size: 1,
+        allocated_size: 0,
+        modified: None,
+        accessed: None,
+        uid: None,
+        gid: None,
+        inode: None,
+        multiple_links: false,
+    };
+    snapshot
+        .tree
+        .update(&FileType::Symlink(symlink, PathBuf::from("a")));
+    snapshot
+        .tree
+        .update(&FileType::Special(PathBuf::from("pipe"), SpecialKind::Fifo));
+    let mut written = Vec::new();
+    snapshot.write_ncdu(&mut written).unwrap();
+    let reread = Snapshot::read_ncdu(&written[..]).unwrap();
+    assert_eq!(reread.tree.total_size, 270);
+    assert_eq!(reread.tree.hard_links, 1);
+    assert_eq!(
+        reread
+            .tree
+            .at(&["sub".to_string()])
+            .unwrap()
+            .hard_links_size,
+        100
+    );
+    assert_eq!(reread.tree.last_modified, Some(1000));
+    assert_eq!(reread.tree.unreadable_count, 2);
+    assert_eq!(
+        specials(&reread.tree),
+        [
+            ("link".to_string(), SpecialKind::Other),
+            ("pipe".to_string(), SpecialKind::Other),
+            ("to-a".to_string(), SpecialKind::Other),
+        ]
+    );
+}
@@ -0,0 +1,79 @@
+use crate::websocket_handler::{api::OwnerSize, worker::tree::FileTotal};
+use std::{collections::HashMap, sync::Mutex};
+
+/// each id is looked up once, None if it doesn't have a name
+type Names = Mutex<HashMap<u32, Option<String>>>;
+
+/// User and group names from this computer's user database, through NSS so
+/// LDAP and the like work too. They might not match an imported scan from another one.
+#[derive(Debug, Default)]
+pub struct OwnerNames {
+    users: Names,
+    groups: Names,
+}
+
+impl OwnerNames {
+    pub fn get_users(&self, totals: &HashMap<u32, FileTotal>) -> Vec<OwnerSize> {
+        get_owners(totals, &self.users, user_name)
+    }
+
+    pub fn get_groups(&self, totals: &HashMap<u32, FileTotal>) -> Vec<OwnerSize> {
+        get_owners(totals, &self.groups, group_name)
+    }
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> Option<String> {
+    let user = uzers::get_user_by_uid(uid)?;
+    Some(user.name().to_string_lossy().to_string())
+}
+
+#[cfg(not(unix))]
+fn user_name(_uid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> Option<String> {
+    let group = uzers::get_group_by_gid(gid)?;
+    Some(group.name().to_string_lossy().to_string())
+}
+
+#[cfg(not(unix))]
+fn group_name(_gid: u32) -> Option<String> {
+    None
+}
+
+/// Biggest first.
+fn get_owners(
+    totals: &HashMap<u32, FileTotal>,
+    names: &Names,
+    lookup: fn(u32) -> Option<String>,
+) -> Vec<OwnerSize> {
+    let mut names = names.lock().unwrap();
+    let mut owners: Vec<OwnerSize> = totals
+        .iter()
+        .map(|(id, total)| OwnerSize {
+            id: *id,
+            name: names.entry(*id).or_insert_with(|| lookup(*id)).clone(),
+            size: total.size,
+            allocated_size: total.allocated_size,
+            file_count: total.file_count,
+        })
+        .collect();
+
+    owners.sort_unstable_by(|a, b| b.size.cmp(&a.size).then_with(|| a.id.cmp(&b.id)));
+    owners
+}
+
+#[cfg(unix)]
+#[test]
+fn test_owner_names() {
+    let mut totals = HashMap::new();
+    totals.insert(0, FileTotal::default());
+
+    let names = OwnerNames::default();
+    assert_eq!(names.get_users(&totals)[0].name.as_deref(), Some("root"));
+    // "wheel" on some systems
+    assert!(names.get_groups(&totals)[0].name.is_some());
+}
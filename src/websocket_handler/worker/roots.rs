@@ -0,0 +1,202 @@
+//! Where each path in the tree is on disk.
+
+use super::get_components;
+use log::info;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The paths being scanned. With more than one the top of the tree is virtual,
+/// with a directory for each root named after it.
+#[derive(Debug, Clone)]
+pub struct Roots {
+    /// tree path of each root, just `[]` for a single one
+    roots: Vec<(Vec<String>, PathBuf)>,
+}
+
+impl Roots {
+    /// Paths inside another one are left out, they'd be counted twice.
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let paths = without_nested(paths);
+        if paths.len() == 1 {
+            return Self {
+                roots: vec![(Vec::new(), paths.into_iter().next().unwrap())],
+            };
+        }
+
+        let mut roots: Vec<(Vec<String>, PathBuf)> = Vec::new();
+        for path in paths {
+            let name = root_name(&path);
+
+            // two roots can end in the same name, like /a/src and /b/src
+            let mut unique_name = name.clone();
+            let mut n = 1;
+            while roots.iter().any(|(root, _)| root[0] == unique_name) {
+                n += 1;
+                unique_name = format!("{} ({})", name, n);
+            }
+
+            roots.push((vec![unique_name], path));
+        }
+
+        Self { roots }
+    }
+
+    /// Whether the top of the tree is made up rather than a directory on disk.
+    pub fn is_virtual(&self) -> bool {
+        self.roots.iter().all(|(root, _)| !root.is_empty())
+    }
+
+    /// Each root's path in the tree and on disk.
+    pub fn iter(&self) -> impl Iterator<Item = (&[String], &Path)> {
+        self.roots
+            .iter()
+            .map(|(root, root_path)| (&root[..], root_path.as_path()))
+    }
+
+    /// Where `path` in the tree is on disk, None for the virtual top level.
+    pub fn full_path(&self, path: &[String]) -> Option<PathBuf> {
+        let (root, root_path) = self.roots.iter().find(|(root, _)| path.starts_with(root))?;
+
+        let mut full_path = root_path.clone();
+        full_path.extend(&path[root.len()..]);
+        Some(full_path)
+    }
+
+    /// Where `full_path` on disk is in the tree, the opposite of `full_path`.
+    pub fn tree_path(&self, full_path: &Path) -> Option<Vec<String>> {
+        self.roots.iter().find_map(|(root, root_path)| {
+            let relative = full_path.strip_prefix(root_path).ok()?;
+            let mut path = root.clone();
+            path.extend(get_components(relative));
+            Some(path)
+        })
+    }
+
+    /// The same roots by their real paths, which some platforms
+    /// report changes with whatever path was watched.
+    pub fn canonicalize(&self) -> Self {
+        let roots = self
+            .roots
+            .iter()
+            .map(|(root, root_path)| {
+                let canonical = fs::canonicalize(root_path).unwrap_or_else(|_| root_path.clone());
+                (root.clone(), canonical)
+            })
+            .collect();
+
+        Self { roots }
+    }
+
+    /// What the client shows as the root, nothing for a virtual one.
+    pub fn root_path(&self) -> Vec<String> {
+        if self.is_virtual() {
+            Vec::new()
+        } else {
+            get_components(&self.roots[0].1)
+        }
+    }
+}
+
+fn without_nested(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let canonical: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect();
+
+    paths
+        .into_iter()
+        .enumerate()
+        .filter(|(i, path)| {
+            // the same path twice keeps the first
+            let outer = canonical.iter().enumerate().find(|(j, other)| {
+                i != j && canonical[*i].starts_with(other) && (canonical[*i] != **other || j < i)
+            });
+
+            if let Some((_, outer)) = outer {
+                info!("{:?} is already scanned as part of {:?}", path, outer);
+            }
+            outer.is_none()
+        })
+        .map(|(_, path)| path)
+        .collect()
+}
+
+// "/home/user/" is "user", "/" and "." are named by what they really are
+fn root_name(path: &Path) -> String {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    if let Some(name) = canonical.file_name() {
+        return name.to_string_lossy().to_string();
+    }
+
+    // "/" or "C:\"
+    let name = canonical
+        .to_string_lossy()
+        .trim_matches(|c| c == '/' || c == '\\')
+        .to_string();
+
+    if name.is_empty() {
+        "root".to_string()
+    } else {
+        name
+    }
+}
+
+#[test]
+fn test_roots() {
+    let single = Roots::new(vec![PathBuf::from("/a/src")]);
+    assert!(!single.is_virtual());
+    assert_eq!(
+        single.full_path(&["b".to_string()]),
+        Some(PathBuf::from("/a/src/b"))
+    );
+
+    let roots = Roots::new(vec![
+        PathBuf::from("/a/src"),
+        PathBuf::from("/b/src"),
+        PathBuf::from("/c"),
+    ]);
+    assert!(roots.is_virtual());
+    assert!(roots.root_path().is_empty());
+
+    let names: Vec<&[String]> = roots.iter().map(|(root, _)| root).collect();
+    assert_eq!(
+        names,
+        vec![
+            &["src".to_string()][..],
+            &["src (2)".to_string()][..],
+            &["c".to_string()][..],
+        ]
+    );
+
+    assert_eq!(roots.full_path(&[]), None);
+    assert_eq!(
+        roots.full_path(&["src (2)".to_string(), "c".to_string()]),
+        Some(PathBuf::from("/b/src/c"))
+    );
+    assert_eq!(
+        roots.full_path(&["c".to_string()]),
+        Some(PathBuf::from("/c"))
+    );
+    assert_eq!(root_name(Path::new("/")), "root");
+
+    assert_eq!(
+        roots.tree_path(Path::new("/b/src/c")),
+        Some(vec!["src (2)".to_string(), "c".to_string()])
+    );
+    assert_eq!(single.tree_path(Path::new("/a/src")), Some(Vec::new()));
+    assert_eq!(single.tree_path(Path::new("/b")), None);
+
+    // already in /a
+    let nested = Roots::new(vec![
+        PathBuf::from("/a/src"),
+        PathBuf::from("/a"),
+        PathBuf::from("/b"),
+        PathBuf::from("/a"),
+    ]);
+    let paths: Vec<&Path> = nested.iter().map(|(_, root_path)| root_path).collect();
+    assert_eq!(paths, [Path::new("/a"), Path::new("/b")]);
+    assert!(!Roots::new(vec![PathBuf::from("/a/src"), PathBuf::from("/a")]).is_virtual());
+}
@@ -0,0 +1,111 @@
+use super::Directory;
+use failure::{bail, Error, ResultExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A finished scan as written to disk.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub root_path: PathBuf,
+    pub tree: Directory,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SnapshotFormat {
+    /// our own format, `Snapshot` as is
+    Json,
+    /// ncdu's `-o` export format
+    Ncdu,
+}
+
+impl FromStr for SnapshotFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "ncdu" => Ok(Self::Ncdu),
+            _ => bail!("unknown format {:?}", s),
+        }
+    }
+}
+
+impl Snapshot {
+    /// Reads either format, ncdu exports are a json array instead of an object.
+    pub fn read(file_path: &Path) -> Result<Self, Error> {
+        let file = File::open(file_path).with_context(|_| format!("opening {:?}", file_path))?;
+        let mut reader = BufReader::new(file);
+
+        let first_byte = reader
+            .fill_buf()?
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .copied();
+
+        let snapshot = if first_byte == Some(b'[') {
+            Self::read_ncdu(reader)
+        } else {
+            read_json(reader).map(|mut snapshot: Self| {
+                snapshot.tree.recount_extensions();
+                snapshot
+            })
+        }
+        .with_context(|_| format!("reading {:?}", file_path))?;
+
+        Ok(snapshot)
+    }
+
+    pub fn write(&self, file_path: &Path, format: SnapshotFormat) -> Result<(), Error> {
+        let file = File::create(file_path).with_context(|_| format!("creating {:?}", file_path))?;
+        let mut writer = BufWriter::new(file);
+
+        match format {
+            SnapshotFormat::Json => serde_json::to_writer(&mut writer, self).map_err(Error::from),
+            SnapshotFormat::Ncdu => self.write_ncdu(&mut writer),
+        }
+        // dropping it would flush without saying if that failed
+        .and_then(|()| writer.flush().map_err(Error::from))
+        .with_context(|_| format!("writing {:?}", file_path))?;
+
+        Ok(())
+    }
+}
+
+/// `serde_json::from_reader` stops at 128 levels of nesting,
+/// and every directory is two of them. This grows the stack as it goes instead.
+pub fn read_json<R: Read, T: DeserializeOwned>(reader: R) -> Result<T, Error> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer.disable_recursion_limit();
+
+    let value = T::deserialize(serde_stacker::Deserializer::new(&mut deserializer))?;
+    deserializer.end()?;
+
+    Ok(value)
+}
+
+#[test]
+fn test_read_deep() {
+    use super::{DirStatus, FileType};
+
+    let mut path = PathBuf::new();
+    let mut tree = Directory::new();
+    for _ in 0..100 {
+        path.push("d");
+        tree.update(&FileType::Dir(path.clone(), DirStatus::Started(None)));
+    }
+
+    let snapshot = Snapshot {
+        root_path: PathBuf::from("/"),
+        tree,
+    };
+    let written = serde_json::to_vec(&snapshot).unwrap();
+    let read: Snapshot = read_json(&written[..]).unwrap();
+
+    assert_eq!(read.tree.dir_count, 100);
+}
@@ -1,20 +1,154 @@
 #![allow(dead_code)]
 
 use crate::websocket_handler::{
-    api::{Entry, UpdatingStatus},
-    worker::walker::{DirStatus, FileSize, FileType},
+    api::{size_change, AgeHistogram, Entry, SpecialKind, UpdatingStatus},
+    worker::{
+        extensions::get_extension,
+        hard_links::HardLinks,
+        walker::{unix_time, walk, DirStatus, FileSize, FileType, SkipReason, WalkOptions},
+    },
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    path::{Path, PathBuf},
+    time::SystemTime,
 };
-use std::{collections::HashMap, path::Path};
 
 // TODO make get_total_size cache!
 // maybe entries_mut sets dirty flag? then all entries_mut will have to be recomputed
 // maybe subscribe to a certain folder's events and call a think function to calculate?
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInfo {
+    pub size: u64,
+    pub allocated_size: u64,
+    /// another link to a file counted somewhere else
+    pub hard_link: bool,
+    /// size in the scan this one was compared to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_size: Option<u64>,
+    /// seconds since the unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessed: Option<u64>,
+    /// so the owner totals can be taken back out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// (device, inode) of files that can be found more than once, to tell which ones go together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inode: Option<(u64, u64)>,
+    /// more than one link on disk, not just reachable through a symlink
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub multiple_links: bool,
+}
+
+impl FileInfo {
+    /// As the walker sent it, without a path.
+    fn file_size(&self) -> FileSize {
+        FileSize {
+            path: PathBuf::new(),
+            size: self.size,
+            allocated_size: self.allocated_size,
+            modified: self.modified,
+            accessed: self.accessed,
+            uid: self.uid,
+            gid: self.gid,
+            inode: self.inode,
+            multiple_links: self.multiple_links,
+        }
+    }
+}
+
+/// A symlink that wasn't followed, its own size isn't in any total.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymlinkInfo {
+    pub target: String,
+    pub size: u64,
+}
+
+/// What the files of one user, group or extension add up to.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTotal {
+    pub size: u64,
+    pub allocated_size: u64,
+    pub file_count: u64,
+}
+
+impl FileTotal {
+    fn add(&mut self, file: &FileSize) {
+        self.size += file.size;
+        self.allocated_size += file.allocated_size;
+        self.file_count += 1;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Directory {
     pub updating: UpdatingStatus,
     pub total_size: u64,
+    pub total_allocated_size: u64,
+    /// number of extra links to files counted somewhere else
+    pub hard_links: u64,
+    /// size of those links that wasn't added to `total_size`
+    pub hard_links_size: u64,
+    /// set when the walker didn't go inside
+    pub skipped: Option<SkipReason>,
+    /// `total_size` in the scan this one was compared to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_size: Option<u64>,
+    /// recursive count of files, extra hard links not included
+    #[serde(default)]
+    pub file_count: u64,
+    /// recursive count of directories, not including this one
+    #[serde(default)]
+    pub dir_count: u64,
+    /// newest `modified` of any file inside, extra hard links not included
+    #[serde(default)]
+    pub last_modified: Option<u64>,
+    #[serde(default)]
+    pub last_accessed: Option<u64>,
+    /// by the newer of each file's `modified` and `accessed`
+    #[serde(default)]
+    pub age_histogram: AgeHistogram,
+    /// by uid, extra hard links not included
+    #[serde(default)]
+    pub users: HashMap<u32, FileTotal>,
+    /// by gid
+    #[serde(default)]
+    pub groups: HashMap<u32, FileTotal>,
+    /// by `get_extension`, extra hard links not included.
+    /// Not saved, `recount_extensions` fills them in again
+    #[serde(skip)]
+    pub extensions: HashMap<String, FileTotal>,
+    /// why this directory couldn't be listed, whatever is inside is missing from the totals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// recursive count of files and directories that couldn't be read, including this one
+    #[serde(default)]
+    pub unreadable_count: u64,
+    /// this directory's own, changes when something directly inside is added or removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
     entries: HashMap<String, Directory>,
+    files: HashMap<String, FileInfo>,
+    /// files that couldn't be read, name to reason
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    errors: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    symlinks: HashMap<String, SymlinkInfo>,
+    /// sockets, fifos and devices
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    specials: HashMap<String, SpecialKind>,
 }
 
 impl Default for Directory {
@@ -28,83 +162,716 @@ impl Directory {
         Self {
             updating: UpdatingStatus::Idle,
             total_size: 0,
+            total_allocated_size: 0,
+            hard_links: 0,
+            hard_links_size: 0,
+            skipped: None,
+            previous_size: None,
+            file_count: 0,
+            dir_count: 0,
+            last_modified: None,
+            last_accessed: None,
+            age_histogram: AgeHistogram::default(),
+            users: HashMap::new(),
+            groups: HashMap::new(),
+            extensions: HashMap::new(),
+            error: None,
+            unreadable_count: 0,
+            modified: None,
             entries: HashMap::new(),
+            files: HashMap::new(),
+            errors: HashMap::new(),
+            symlinks: HashMap::new(),
+            specials: HashMap::new(),
         }
     }
 
-    pub fn at_mut(&mut self, components: &[String]) -> Option<&mut Self> {
+    /// Walk `root_path` to the end without any websocket updates.
+    pub fn scan(root_path: PathBuf, options: &WalkOptions) -> Self {
+        let mut tree = Self::new();
+        for file_type in walk(root_path, options) {
+            tree.update(&file_type);
+        }
+
+        tree
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &Self)> {
+        self.entries.iter()
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (&String, &FileInfo)> {
+        self.files.iter()
+    }
+
+    pub fn file(&self, name: &str) -> Option<&FileInfo> {
+        self.files.get(name)
+    }
+
+    pub fn symlinks(&self) -> impl Iterator<Item = (&String, &SymlinkInfo)> {
+        self.symlinks.iter()
+    }
+
+    pub fn specials(&self) -> impl Iterator<Item = (&String, &SpecialKind)> {
+        self.specials.iter()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.errors.iter()
+    }
+
+    /// Fill in `previous_size` everywhere from an older scan of the same root.
+    pub fn compare(&mut self, previous: &Self) {
+        self.compare_with(Some(previous));
+    }
+
+    // anything that didn't exist back then was 0
+    fn compare_with(&mut self, previous: Option<&Self>) {
+        self.previous_size = Some(previous.map_or(0, |previous| previous.total_size));
+
+        for (name, file) in &mut self.files {
+            let previous_file = previous.and_then(|previous| previous.files.get(name));
+            file.previous_size = Some(previous_file.map_or(0, |previous| previous.size));
+        }
+
+        for (name, child) in &mut self.entries {
+            child.compare_with(previous.and_then(|previous| previous.entries.get(name)));
+        }
+    }
+
+    /// Undo `compare`, the sizes it filled in only mean something next to that scan.
+    pub fn clear_comparison(&mut self) {
+        self.previous_size = None;
+
+        for file in self.files.values_mut() {
+            file.previous_size = None;
+        }
+
+        for child in self.entries.values_mut() {
+            child.clear_comparison();
+        }
+    }
+
+    pub fn at(&self, components: &[String]) -> Option<&Self> {
         let mut current = self;
         for component in components {
-            current = current.entries.get_mut(component)?;
+            current = current.entries.get(component)?;
         }
 
         Some(current)
     }
 
-    fn set_updating(&mut self, components: &[String], updating: UpdatingStatus) {
+    pub fn at_mut(&mut self, components: &[String]) -> Option<&mut Self> {
         let mut current = self;
         for component in components {
-            current = current
-                .entries
-                .entry(component.clone())
-                .or_insert_with(Self::new);
+            current = current.entries.get_mut(component)?;
+        }
+
+        Some(current)
+    }
+
+    fn at_or_insert(&mut self, components: &[String]) -> &mut Self {
+        for depth in 0..components.len() {
+            if self.at(&components[..=depth]).is_none() {
+                // the parent was there or was inserted just before
+                if let Some(parent) = self.for_each_on_path(&components[..depth], |dir| {
+                    dir.dir_count += 1;
+                }) {
+                    parent
+                        .entries
+                        .insert(components[depth].clone(), Self::new());
+                }
+            }
         }
 
-        current.updating = updating;
+        self.at_mut(components).unwrap()
     }
 
-    fn add_file(&mut self, components: &[String], size: u64) {
+    /// Calls `f` on the root and every directory down to `components`,
+    /// returning the last one. Nothing is called if it isn't in the tree.
+    fn for_each_on_path<F>(&mut self, components: &[String], mut f: F) -> Option<&mut Self>
+    where
+        F: FnMut(&mut Self),
+    {
         // <root>/hello/world/
+        self.at(components)?;
 
         let mut current = self;
-        // root tree total_size += size
-        current.total_size += size;
+        f(current);
 
-        // update 'hello' then 'world'
+        // 'hello' then 'world'
         for component in components {
-            // this unwrap is ok because we do set_updating ALWAYS before add_file
-            current = current.entries.get_mut(component).unwrap();
+            current = current.entries.get_mut(component)?;
+            f(current);
+        }
+
+        Some(current)
+    }
+
+    /// Whether `components` is below a directory that wasn't walked,
+    /// where nothing should be added.
+    pub fn is_inside_skipped(&self, components: &[String]) -> bool {
+        let mut current = self;
+        for component in components {
+            if current.skipped.is_some() {
+                return true;
+            }
+
+            match current.entries.get(component) {
+                Some(child) => current = child,
+                None => return false,
+            }
+        }
+
+        false
+    }
+
+    /// Take `other`'s totals back out, `last_modified` and `last_accessed` can't be.
+    fn subtract(&mut self, other: &Self) {
+        self.total_size = self.total_size.saturating_sub(other.total_size);
+        self.total_allocated_size = self
+            .total_allocated_size
+            .saturating_sub(other.total_allocated_size);
+        self.hard_links = self.hard_links.saturating_sub(other.hard_links);
+        self.hard_links_size = self.hard_links_size.saturating_sub(other.hard_links_size);
+        self.file_count = self.file_count.saturating_sub(other.file_count);
+        self.dir_count = self.dir_count.saturating_sub(other.dir_count);
+        self.unreadable_count = self.unreadable_count.saturating_sub(other.unreadable_count);
+        self.age_histogram.subtract(&other.age_histogram);
+
+        subtract_totals(&mut self.users, &other.users);
+        subtract_totals(&mut self.groups, &other.groups);
+        subtract_totals(&mut self.extensions, &other.extensions);
+    }
+
+    /// Forget whatever is at `components` and take it out of the totals above.
+    pub fn remove(&mut self, components: &[String]) {
+        if let Some((name, parents)) = components.split_last() {
+            if let Some(removed) = self
+                .at_mut(parents)
+                .and_then(|parent| parent.take_child(name))
+            {
+                self.for_each_on_path(parents, |dir| dir.subtract(&removed));
+            }
+        } else {
+            *self = Self::new();
+        }
+    }
+
+    /// The opposite of `subtract`.
+    fn add_all(&mut self, other: &Self) {
+        self.total_size += other.total_size;
+        self.total_allocated_size += other.total_allocated_size;
+        self.hard_links += other.hard_links;
+        self.hard_links_size += other.hard_links_size;
+        self.file_count += other.file_count;
+        self.dir_count += other.dir_count;
+        self.unreadable_count += other.unreadable_count;
+        self.last_modified = self.last_modified.max(other.last_modified);
+        self.last_accessed = self.last_accessed.max(other.last_accessed);
+        self.age_histogram.add_all(&other.age_histogram);
+
+        add_totals(&mut self.users, &other.users);
+        add_totals(&mut self.groups, &other.groups);
+        add_totals(&mut self.extensions, &other.extensions);
+    }
+
+    /// Put a whole directory directly inside this one, like a cached root.
+    pub fn insert(&mut self, name: String, dir: Self) {
+        self.add_all(&dir);
+        self.dir_count += 1;
+        self.entries.insert(name, dir);
+    }
+
+    /// Returns what the child added to the totals, only those fields are used.
+    fn take_child(&mut self, name: &str) -> Option<Self> {
+        if let Some(mut dir) = self.entries.remove(name) {
+            dir.dir_count += 1;
+            return Some(dir);
+        }
+
+        let mut removed = Self::new();
+
+        if let Some(file) = self.files.remove(name) {
+            let file_size = file.file_size();
+            if file.hard_link {
+                removed.add_hard_link(&[], name, &file_size);
+            } else {
+                removed.add_file(&[], name, &file_size);
+            }
+        } else if self.errors.remove(name).is_some() {
+            removed.unreadable_count = 1;
+        } else {
+            // these aren't in any total
+            self.symlinks.remove(name);
+            self.specials.remove(name);
+            return None;
+        }
+
+        Some(removed)
+    }
+
+    /// `remove`, counting another link to any file that was counted there.
+    /// Returns the files that are counted now, with their paths.
+    pub fn remove_linked(
+        &mut self,
+        components: &[String],
+        hard_links: &mut HardLinks,
+    ) -> Vec<FileSize> {
+        self.remove(components);
+
+        hard_links
+            .remove_below(components)
+            .iter()
+            .filter_map(|path| self.promote_hard_link(path))
+            .collect()
+    }
+
+    /// Count the extra link at `components` as the file itself.
+    fn promote_hard_link(&mut self, components: &[String]) -> Option<FileSize> {
+        let (name, parents) = components.split_last()?;
+        let (mut file, previous_size) = match self.at(parents)?.files.get(name) {
+            Some(file) if file.hard_link => (file.file_size(), file.previous_size),
+            _ => return None,
+        };
+
+        self.for_each_on_path(parents, |dir| {
+            dir.hard_links = dir.hard_links.saturating_sub(1);
+            dir.hard_links_size = dir.hard_links_size.saturating_sub(file.size);
+        });
+        self.add_file(parents, name, &file);
+
+        if let Some(promoted) = self.at_mut(parents).and_then(|dir| dir.files.get_mut(name)) {
+            promoted.previous_size = previous_size;
+        }
+
+        file.path = components.iter().collect();
+        Some(file)
+    }
+
+    /// `compare` for just what's at `components`, after it was walked again.
+    pub fn compare_at(&mut self, components: &[String], previous: &Self) {
+        if let Some(dir) = self.at_mut(components) {
+            dir.compare_with(previous.at(components));
+        } else if let Some((name, parents)) = components.split_last() {
+            // a file
+            let previous_size = previous
+                .at(parents)
+                .and_then(|dir| dir.files.get(name))
+                .map_or(0, |file| file.size);
+            if let Some(file) = self.at_mut(parents).and_then(|dir| dir.files.get_mut(name)) {
+                file.previous_size = Some(previous_size);
+            }
+        }
+    }
+
+    /// Fill in `extensions` everywhere, for a tree that was read back from disk.
+    pub fn recount_extensions(&mut self) {
+        let mut extensions: HashMap<String, FileTotal> = HashMap::new();
+
+        for (name, file) in &self.files {
+            if !file.hard_link {
+                let total = extensions.entry(get_extension(name)).or_default();
+                total.size += file.size;
+                total.allocated_size += file.allocated_size;
+                total.file_count += 1;
+            }
+        }
+
+        for child in self.entries.values_mut() {
+            child.recount_extensions();
+            add_totals(&mut extensions, &child.extensions);
+        }
+
+        self.extensions = extensions;
+    }
+
+    /// Sort every file into `age_histogram` again as of now,
+    /// for a tree loaded from the cache that was scanned a while ago.
+    pub fn recount_ages(&mut self) {
+        let now = unix_time(Ok(SystemTime::now())).unwrap_or(0);
+        self.recount_ages_at(now);
+    }
+
+    fn recount_ages_at(&mut self, now: u64) {
+        let mut age_histogram = AgeHistogram::default();
+
+        for file in self.files.values().filter(|file| !file.hard_link) {
+            if let Some(touched) = file.modified.max(file.accessed) {
+                age_histogram.add(touched, now, file.size);
+            }
+        }
+
+        for child in self.entries.values_mut() {
+            child.recount_ages_at(now);
+            age_histogram.add_all(&child.age_histogram);
+        }
+
+        self.age_histogram = age_histogram;
+    }
+
+    /// Mark everything as not checked yet, for a tree loaded from the cache.
+    pub fn set_all_updating(&mut self) {
+        if self.skipped.is_none() {
+            self.updating = UpdatingStatus::Updating;
+        }
+
+        for child in self.entries.values_mut() {
+            child.set_all_updating();
+        }
+    }
+
+    /// Every directory that was read with its `modified`, parents before children.
+    pub fn directory_modified(&self) -> Vec<(Vec<String>, Option<u64>)> {
+        let mut directories = Vec::new();
+        self.add_directory_modified(&mut Vec::new(), &mut directories);
+        directories
+    }
+
+    fn add_directory_modified(
+        &self,
+        path: &mut Vec<String>,
+        directories: &mut Vec<(Vec<String>, Option<u64>)>,
+    ) {
+        if self.skipped.is_some() {
+            return;
+        }
+
+        directories.push((path.clone(), self.modified));
+
+        for (name, child) in &self.entries {
+            path.push(name.clone());
+            child.add_directory_modified(path, directories);
+            path.pop();
+        }
+    }
+
+    /// Forget everything directly inside `components` that isn't a directory,
+    /// to read it again, counting other links to its files like `remove_linked`.
+    pub fn remove_files(&mut self, components: &[String], hard_links: &mut HardLinks) {
+        let names: Vec<String> = match self.at(components) {
+            Some(dir) => dir
+                .files
+                .keys()
+                .chain(dir.errors.keys())
+                .chain(dir.symlinks.keys())
+                .chain(dir.specials.keys())
+                .cloned()
+                .collect(),
+            None => return,
+        };
+
+        let mut path = components.to_vec();
+        for name in names {
+            path.push(name);
+            self.remove(&path);
+            path.pop();
+        }
+
+        for path in hard_links.remove_in(components) {
+            self.promote_hard_link(&path);
+        }
+
+        // the directory's own error
+        if let Some(dir) = self.at_mut(components) {
+            if dir.error.take().is_some() {
+                self.for_each_on_path(components, |dir| {
+                    dir.unreadable_count = dir.unreadable_count.saturating_sub(1);
+                });
+            }
+        }
+    }
+
+    /// The directory at `components` is up to date.
+    pub fn set_validated(&mut self, components: &[String], modified: Option<u64>) {
+        if let Some(dir) = self.at_mut(components) {
+            dir.updating = UpdatingStatus::Finished;
+            dir.modified = modified;
+        }
+    }
+
+    fn set_updating(&mut self, components: &[String], updating: UpdatingStatus) {
+        self.at_or_insert(components).updating = updating;
+    }
 
-            current.total_size += size;
+    fn set_skipped(&mut self, components: &[String], reason: SkipReason) {
+        let current = self.at_or_insert(components);
+        current.updating = UpdatingStatus::Finished;
+        current.skipped = Some(reason);
+    }
+
+    fn set_error(&mut self, components: &[String], message: &str) {
+        if let Some(dir) = self.at_mut(components) {
+            // a directory we got into but couldn't list
+            dir.error = Some(message.to_string());
+            self.for_each_on_path(components, |dir| dir.unreadable_count += 1);
+        } else if let Some((name, components)) = components.split_last() {
+            self.at_or_insert(components);
+            if let Some(current) =
+                self.for_each_on_path(components, |dir| dir.unreadable_count += 1)
+            {
+                current.errors.insert(name.clone(), message.to_string());
+            }
         }
     }
 
+    fn add_file(&mut self, components: &[String], name: &str, file: &FileSize) {
+        let now = unix_time(Ok(SystemTime::now())).unwrap_or(0);
+        // None is less than any Some
+        let touched = file.modified.max(file.accessed);
+        let extension = get_extension(name);
+
+        let added = self.for_each_on_path(components, |dir| {
+            dir.total_size += file.size;
+            dir.total_allocated_size += file.allocated_size;
+            dir.file_count += 1;
+            dir.last_modified = dir.last_modified.max(file.modified);
+            dir.last_accessed = dir.last_accessed.max(file.accessed);
+            if let Some(touched) = touched {
+                dir.age_histogram.add(touched, now, file.size);
+            }
+            for (owners, id) in &mut [(&mut dir.users, file.uid), (&mut dir.groups, file.gid)] {
+                if let Some(id) = id {
+                    owners.entry(*id).or_default().add(file);
+                }
+            }
+            dir.extensions
+                .entry(extension.clone())
+                .or_default()
+                .add(file);
+        });
+
+        if let Some(current) = added {
+            current.files.insert(
+                name.to_string(),
+                FileInfo {
+                    size: file.size,
+                    allocated_size: file.allocated_size,
+                    hard_link: false,
+                    previous_size: None,
+                    modified: file.modified,
+                    accessed: file.accessed,
+                    uid: file.uid,
+                    gid: file.gid,
+                    inode: file.inode,
+                    multiple_links: file.multiple_links,
+                },
+            );
+        } else {
+            debug!("{:?} isn't in the tree for {:?}", components, file.path);
+        }
+    }
+
+    fn add_hard_link(&mut self, components: &[String], name: &str, file: &FileSize) {
+        let added = self.for_each_on_path(components, |dir| {
+            dir.hard_links += 1;
+            dir.hard_links_size += file.size;
+        });
+
+        if let Some(current) = added {
+            current.files.insert(
+                name.to_string(),
+                FileInfo {
+                    size: file.size,
+                    allocated_size: file.allocated_size,
+                    hard_link: true,
+                    previous_size: None,
+                    modified: file.modified,
+                    accessed: file.accessed,
+                    uid: file.uid,
+                    gid: file.gid,
+                    inode: file.inode,
+                    multiple_links: file.multiple_links,
+                },
+            );
+        } else {
+            debug!("{:?} isn't in the tree for {:?}", components, file.path);
+        }
+    }
+
+    fn add_symlink(&mut self, components: &[String], name: &str, file: &FileSize, target: &Path) {
+        self.at_or_insert(components).symlinks.insert(
+            name.to_string(),
+            SymlinkInfo {
+                target: target.to_string_lossy().to_string(),
+                size: file.size,
+            },
+        );
+    }
+
     pub fn update(&mut self, file_type: &FileType) {
         match file_type {
             FileType::Dir(path, status) => {
                 let components = get_components(&path);
-                let updating = if let DirStatus::Started = status {
-                    UpdatingStatus::Updating
-                } else {
-                    UpdatingStatus::Finished
-                };
+                match status {
+                    DirStatus::Started(modified) => {
+                        let dir = self.at_or_insert(&components);
+                        dir.updating = UpdatingStatus::Updating;
+                        dir.modified = *modified;
+                    }
+                    DirStatus::Finished => {
+                        self.set_updating(&components, UpdatingStatus::Finished);
+                    }
+                }
+            }
+
+            FileType::File(file) => {
+                if let Some((components, name)) = parent_and_name(&file.path) {
+                    self.add_file(&components, &name, file);
+                }
+            }
+
+            FileType::HardLink(file) => {
+                if let Some((components, name)) = parent_and_name(&file.path) {
+                    self.add_hard_link(&components, &name, file);
+                }
+            }
+
+            FileType::Symlink(file, target) => {
+                if let Some((components, name)) = parent_and_name(&file.path) {
+                    self.add_symlink(&components, &name, file, target);
+                }
+            }
 
-                self.set_updating(&components, updating);
+            FileType::Special(path, kind) => {
+                if let Some((components, name)) = parent_and_name(path) {
+                    self.at_or_insert(&components).specials.insert(name, *kind);
+                }
             }
 
-            FileType::File(FileSize(path, size)) => {
+            FileType::Skipped(path, reason) => {
                 let components = get_components(&path);
-                // remove filename
-                self.add_file(&components[..components.len() - 1], *size);
+                self.set_skipped(&components, *reason);
             }
+
+            FileType::Error(path, message) => {
+                let components = get_components(&path);
+                self.set_error(&components, message);
+            }
+        }
+    }
+
+    pub fn get_entry_directory(&self, path: Vec<String>) -> Entry {
+        match self.at(&path) {
+            Some(dir) => dir.to_entry(path),
+            None => Self::new().to_entry(path),
         }
     }
 
-    pub fn get_entry_directory(&mut self, path: Vec<String>) -> Entry {
-        let (size, updating) = self
-            .at_mut(&path)
-            .map_or((0, UpdatingStatus::Idle), |entry| {
-                (entry.total_size, entry.updating)
+    /// Directory listing from the tree alone, for when the files aren't on this disk.
+    pub fn get_entries(&self, path: &[String]) -> Vec<Entry> {
+        let child_path = |name: &String| {
+            let mut child_path = path.to_vec();
+            child_path.push(name.clone());
+            child_path
+        };
+
+        self.at(path).map_or_else(Vec::new, |dir| {
+            let directories = dir
+                .entries
+                .iter()
+                .map(|(name, child)| child.to_entry(child_path(name)));
+
+            let files = dir.files.iter().map(|(name, file)| Entry::File {
+                path: child_path(name),
+                size: file.size,
+                allocated_size: file.allocated_size,
+                previous_size: file.previous_size,
+                size_change: size_change(file.size, file.previous_size),
+                modified: file.modified,
+                accessed: file.accessed,
             });
 
+            let errors = dir.errors.iter().map(|(name, message)| Entry::Error {
+                path: child_path(name),
+                message: message.clone(),
+            });
+
+            let symlinks = dir.symlinks.iter().map(|(name, symlink)| Entry::Symlink {
+                path: child_path(name),
+                target: symlink.target.clone(),
+                size: symlink.size,
+            });
+
+            let specials = dir.specials.iter().map(|(name, kind)| Entry::Special {
+                path: child_path(name),
+                kind: *kind,
+            });
+
+            directories
+                .chain(files)
+                .chain(symlinks)
+                .chain(specials)
+                .chain(errors)
+                .collect()
+        })
+    }
+
+    fn to_entry(&self, path: Vec<String>) -> Entry {
+        match self.skipped {
+            Some(SkipReason::MountPoint) => return Entry::MountPoint { path },
+            Some(SkipReason::Excluded) => return Entry::Excluded { path },
+            None => {}
+        }
+
         Entry::Directory {
             path,
-            size,
-            updating,
+            size: self.total_size,
+            allocated_size: self.total_allocated_size,
+            updating: self.updating,
+            hard_links: self.hard_links,
+            hard_links_size: self.hard_links_size,
+            previous_size: self.previous_size,
+            size_change: size_change(self.total_size, self.previous_size),
+            file_count: self.file_count,
+            dir_count: self.dir_count,
+            last_modified: self.last_modified,
+            last_accessed: self.last_accessed,
+            age_histogram: self.age_histogram,
+            error: self.error.clone(),
+            unreadable_count: self.unreadable_count,
+        }
+    }
+}
+
+fn add_totals<K: Clone + Eq + Hash>(
+    totals: &mut HashMap<K, FileTotal>,
+    other_totals: &HashMap<K, FileTotal>,
+) {
+    for (key, other_total) in other_totals {
+        let total = totals.entry(key.clone()).or_default();
+        total.size += other_total.size;
+        total.allocated_size += other_total.allocated_size;
+        total.file_count += other_total.file_count;
+    }
+}
+
+// the ones left without any files are taken out
+fn subtract_totals<K: Eq + Hash>(
+    totals: &mut HashMap<K, FileTotal>,
+    other_totals: &HashMap<K, FileTotal>,
+) {
+    for (key, other_total) in other_totals {
+        if let Some(total) = totals.get_mut(key) {
+            total.size = total.size.saturating_sub(other_total.size);
+            total.allocated_size = total
+                .allocated_size
+                .saturating_sub(other_total.allocated_size);
+            total.file_count = total.file_count.saturating_sub(other_total.file_count);
+            if total.file_count == 0 {
+                totals.remove(key);
+            }
         }
     }
 }
 
+/// (directory, filename) of anything that isn't a directory, the root has neither.
+fn parent_and_name(path: &Path) -> Option<(Vec<String>, String)> {
+    let mut components = get_components(path);
+    let name = components.pop()?;
+    Some((components, name))
+}
+
 pub fn get_components<B: AsRef<Path>>(path: B) -> Vec<String> {
     path.as_ref()
         .iter()
@@ -115,15 +882,185 @@ pub fn get_components<B: AsRef<Path>>(path: B) -> Vec<String> {
 #[ignore]
 #[test]
 fn test_tree() {
-    use super::walker::walk;
+    use super::walker::{walk, WalkOptions};
 
     crate::logger::initialize(true, false);
 
     let mut t = Directory::new();
 
-    let file_size_stream = walk("src".parse().unwrap());
+    let file_size_stream = walk("src".parse().unwrap(), &WalkOptions::default());
     for file_type in file_size_stream {
         t.update(&file_type);
         println!("{:?} {:#?}", file_type, t);
     }
 }
+
+#[test]
+fn test_tree_counts() {
+    let mut t = Directory::new();
+    t.update(&FileType::Dir("".into(), DirStatus::Started(None)));
+    t.update(&FileType::Dir("a".into(), DirStatus::Started(None)));
+    t.update(&FileType::Dir("a/b".into(), DirStatus::Started(None)));
+    for path in &["a/1", "a/b/2", "a/b/3"] {
+        t.update(&FileType::File(FileSize {
+            path: path.into(),
+            size: 10,
+            allocated_size: 4096,
+            uid: Some(1000),
+            ..FileSize::default()
+        }));
+    }
+    t.update(&FileType::Skipped("c".into(), SkipReason::Excluded));
+
+    assert_eq!((t.file_count, t.dir_count, t.total_size), (3, 3, 30));
+    assert_eq!(t.users[&1000].size, 30);
+    assert!(t.groups.is_empty());
+
+    let a = t.at(&["a".to_string()]).unwrap();
+    assert_eq!((a.file_count, a.dir_count, a.total_size), (3, 1, 30));
+}
+
+#[test]
+fn test_tree_ages() {
+    const DAY: u64 = 24 * 60 * 60;
+
+    let now = unix_time(Ok(SystemTime::now())).unwrap();
+
+    let mut t = Directory::new();
+    t.update(&FileType::Dir("".into(), DirStatus::Started(None)));
+    t.update(&FileType::Dir("a".into(), DirStatus::Started(None)));
+    for (path, modified, accessed) in &[
+        ("a/new", Some(now), None),
+        ("a/read", Some(now - 400 * DAY), Some(now - 2 * DAY)),
+        ("old", Some(now - 400 * DAY), Some(now - 400 * DAY)),
+        ("unknown", None, None),
+    ] {
+        t.update(&FileType::File(FileSize {
+            path: path.into(),
+            size: 10,
+            allocated_size: 10,
+            modified: *modified,
+            accessed: *accessed,
+            ..FileSize::default()
+        }));
+    }
+
+    assert_eq!(t.last_modified, Some(now));
+    assert_eq!(t.age_histogram.0, [10, 10, 0, 0, 10]);
+
+    let a = t.at(&["a".to_string()]).unwrap();
+    assert_eq!(a.last_accessed, Some(now - 2 * DAY));
+    assert_eq!(a.age_histogram.0, [10, 10, 0, 0, 0]);
+}
+
+#[test]
+fn test_tree_errors() {
+    let mut t = Directory::new();
+    t.update(&FileType::Dir("".into(), DirStatus::Started(None)));
+    t.update(&FileType::Dir("a".into(), DirStatus::Started(None)));
+    t.update(&FileType::Error("a".into(), "Permission denied".into()));
+    t.update(&FileType::Error("b".into(), "No such file".into()));
+
+    assert_eq!(t.unreadable_count, 2);
+    assert_eq!(t.error, None);
+
+    let a = t.at(&["a".to_string()]).unwrap();
+    assert_eq!(a.error.as_deref(), Some("Permission denied"));
+    assert_eq!(a.unreadable_count, 1);
+
+    assert!(t
+        .get_entries(&[])
+        .iter()
+        .any(|entry| matches!(entry, Entry::Error { path, .. } if path == &["b"])));
+}
+
+#[test]
+fn test_tree_remove() {
+    let mut t = Directory::new();
+    t.update(&FileType::Dir("".into(), DirStatus::Started(None)));
+    t.update(&FileType::Dir("a".into(), DirStatus::Started(None)));
+    t.update(&FileType::Dir("a/b".into(), DirStatus::Started(None)));
+    for path in &["a/1", "a/b/2", "3"] {
+        t.update(&FileType::File(FileSize {
+            path: path.into(),
+            size: 10,
+            allocated_size: 4096,
+            uid: Some(1000),
+            ..FileSize::default()
+        }));
+    }
+    t.update(&FileType::Error("a/b/4".into(), "Permission denied".into()));
+
+    t.remove(&["a".to_string(), "b".to_string()]);
+    assert_eq!((t.total_size, t.file_count, t.dir_count), (20, 2, 1));
+    assert_eq!(t.unreadable_count, 0);
+    assert_eq!(t.at(&["a".to_string()]).unwrap().total_size, 10);
+    assert_eq!(t.users[&1000].file_count, 2);
+
+    t.remove(&["3".to_string()]);
+    t.remove(&["a".to_string()]);
+    assert_eq!((t.total_size, t.file_count, t.dir_count), (0, 0, 0));
+    assert!(t.users.is_empty());
+    assert!(t.at(&["a".to_string()]).is_none());
+}
+
+#[test]
+fn test_tree_inside_skipped() {
+    let mut t = Directory::new();
+    t.update(&FileType::Dir("".into(), DirStatus::Started(None)));
+    t.update(&FileType::Skipped("a".into(), SkipReason::Excluded));
+    assert!(t.is_inside_skipped(&get_components("a/b")));
+    assert!(!t.is_inside_skipped(&get_components("a")));
+
+    // nothing to add them to, they're left out instead of panicking
+    for path in &["a/b/1", "c/2", ""] {
+        t.update(&FileType::File(FileSize {
+            path: path.into(),
+            size: 10,
+            ..FileSize::default()
+        }));
+    }
+    t.update(&FileType::HardLink(FileSize {
+        path: "a/b/3".into(),
+        size: 10,
+        ..FileSize::default()
+    }));
+    assert_eq!((t.total_size, t.file_count, t.hard_links), (0, 0, 0));
+    assert!(t.at(&get_components("a/b")).is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_tree_rescan_hard_links() {
+    use super::walker::walk_part;
+    use std::fs;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("s1")).unwrap();
+    fs::create_dir(root.join("s2")).unwrap();
+    fs::write(root.join("s1/a"), "hello").unwrap();
+    fs::hard_link(root.join("s1/a"), root.join("s2/b")).unwrap();
+
+    let options = WalkOptions::default();
+    let mut t = Directory::new();
+    let mut hard_links = HardLinks::new();
+    for file_type in walk(root.to_path_buf(), &options) {
+        t.update(&file_type);
+        hard_links.update(&file_type);
+    }
+    assert_eq!((t.total_size, t.hard_links), (5, 1));
+
+    // the counted link is inside one of them and the extra one outside,
+    // rescanning that one has to hand it over
+    for rescanned in &["s1", "s2", "s1"] {
+        t.remove_linked(&[rescanned.to_string()], &mut hard_links);
+        for mut file_type in walk_part(root.join(rescanned), &options, hard_links.counted()) {
+            file_type.add_prefix(Path::new(rescanned));
+            t.update(&file_type);
+            hard_links.update(&file_type);
+        }
+
+        assert_eq!((t.total_size, t.hard_links), (5, 1));
+    }
+}
@@ -0,0 +1,136 @@
+//! Mounted filesystems, for picking one to scan.
+
+use crate::websocket_handler::api::Volume;
+use log::debug;
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+struct MountInfo {
+    mount_point: String,
+    source: String,
+    fs_type: String,
+}
+
+/// Whether `get_volumes` works here, only Linux has a mountinfo.
+pub fn can_list_volumes() -> bool {
+    Path::new(MOUNTINFO).exists()
+}
+
+/// Every mounted filesystem with space on it, by mount point.
+pub fn get_volumes() -> io::Result<Vec<Volume>> {
+    let mountinfo = fs::read_to_string(MOUNTINFO)?;
+
+    // a later mount on the same point hides the earlier one
+    let mut volumes = BTreeMap::new();
+    for mount in parse_mountinfo(&mountinfo) {
+        let space = fs2::total_space(&mount.mount_point).and_then(|total_space| {
+            Ok((
+                total_space,
+                fs2::free_space(&mount.mount_point)?,
+                fs2::available_space(&mount.mount_point)?,
+            ))
+        });
+
+        let (total_space, free_space, available_space) = match space {
+            // proc, sysfs, cgroups and the like
+            Ok((0, _, _)) => continue,
+            Ok(space) => space,
+            Err(e) => {
+                debug!("space of {:?}: {}", mount.mount_point, e);
+                continue;
+            }
+        };
+
+        volumes.insert(
+            mount.mount_point.clone(),
+            Volume {
+                mount_point: mount.mount_point,
+                source: mount.source,
+                fs_type: mount.fs_type,
+                total_space,
+                used_space: total_space.saturating_sub(free_space),
+                available_space,
+            },
+        );
+    }
+
+    Ok(volumes.into_values().collect())
+}
+
+// 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+// the optional fields before "-" can be any number of them
+fn parse_mountinfo(mountinfo: &str) -> Vec<MountInfo> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (mount, filesystem) = line.split_once(" - ")?;
+            let mount_point = mount.split(' ').nth(4)?;
+
+            let mut filesystem = filesystem.split(' ');
+            let fs_type = filesystem.next()?;
+            let source = filesystem.next()?;
+
+            Some(MountInfo {
+                mount_point: unescape(mount_point),
+                source: unescape(source),
+                fs_type: fs_type.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Spaces, tabs, newlines and backslashes come as octal like "\040".
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+
+        if let Some(byte) = octal {
+            unescaped.push(byte);
+            i += 4;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8_lossy(&unescaped).to_string()
+}
+
+#[test]
+fn test_parse_mountinfo() {
+    let mounts = parse_mountinfo(
+        "22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n\
+         23 22 0:21 / /proc rw,nosuid - proc proc rw\n\
+         24 22 8:17 / /media/USB\\040Stick rw master:2 unbindable - vfat /dev/sdb1 rw\n\
+         not a mount",
+    );
+
+    let fields: Vec<(&str, &str, &str)> = mounts
+        .iter()
+        .map(|mount| {
+            (
+                mount.mount_point.as_str(),
+                mount.source.as_str(),
+                mount.fs_type.as_str(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        fields,
+        vec![
+            ("/", "/dev/sda1", "ext4"),
+            ("/proc", "proc", "proc"),
+            ("/media/USB Stick", "/dev/sdb1", "vfat"),
+        ]
+    );
+}
@@ -1,58 +1,511 @@
-use jwalk::WalkDirGeneric;
+use crate::websocket_handler::api::SpecialKind;
+use failure::Error;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use jwalk::{DirEntry, WalkDirGeneric};
 use log::info;
+use serde::{Deserialize, Serialize};
 use std::{
-    fs::Metadata,
-    path::PathBuf,
+    collections::HashSet,
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-#[derive(Debug, Clone)]
-pub struct FileSize(pub PathBuf, pub u64);
+#[derive(Debug, Clone, Default)]
+pub struct FileSize {
+    pub path: PathBuf,
+    /// apparent size, `metadata.len()`
+    pub size: u64,
+    /// space actually used on disk
+    pub allocated_size: u64,
+    /// seconds since the unix epoch, None if the platform doesn't keep them
+    pub modified: Option<u64>,
+    pub accessed: Option<u64>,
+    /// owning user and group, None if the platform doesn't have them
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// (device, inode) of files that can be found more than once,
+    /// ones with more than one link or any when following symlinks
+    pub inode: Option<(u64, u64)>,
+    /// more than one link on disk, not just reachable through a symlink
+    pub multiple_links: bool,
+}
+
+impl FileSize {
+    pub fn new(path: PathBuf, metadata: &Metadata) -> Self {
+        let (uid, gid) = owner(metadata);
+        let inode = hard_link_id(metadata);
+
+        Self {
+            path,
+            size: metadata.len(),
+            allocated_size: allocated_size(metadata),
+            modified: unix_time(metadata.modified()),
+            accessed: unix_time(metadata.accessed()),
+            uid,
+            gid,
+            inode,
+            multiple_links: inode.is_some(),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum DirStatus {
-    Started,
+    /// with the directory's own `modified`, to tell later if anything was added or removed
+    Started(Option<u64>),
     Finished,
 }
 
+/// why a directory wasn't descended into
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SkipReason {
+    /// on another filesystem than the root
+    MountPoint,
+    /// matched an exclude pattern
+    Excluded,
+}
+
 #[derive(Debug, Clone)]
 pub enum FileType {
     File(FileSize),
+    /// another link to a file we've already counted
+    HardLink(FileSize),
     Dir(PathBuf, DirStatus),
+    Skipped(PathBuf, SkipReason),
+    /// a file that couldn't be looked at or a directory that couldn't be listed
+    Error(PathBuf, String),
+    /// a link that wasn't followed, with where it points
+    Symlink(FileSize, PathBuf),
+    /// a socket, fifo or device
+    Special(PathBuf, SpecialKind),
+}
+
+impl FileType {
+    /// For walks that didn't start at the root.
+    pub fn add_prefix(&mut self, prefix: &Path) {
+        let path = match self {
+            Self::File(file) | Self::HardLink(file) | Self::Symlink(file, _) => &mut file.path,
+            Self::Dir(path, _)
+            | Self::Skipped(path, _)
+            | Self::Error(path, _)
+            | Self::Special(path, _) => path,
+        };
+
+        // the walk's root comes out as ""
+        *path = if path.as_os_str().is_empty() {
+            prefix.to_path_buf()
+        } else {
+            prefix.join(&path)
+        };
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// don't descend into directories on other filesystems
+    pub one_file_system: bool,
+    /// gitignore-style patterns for files and directories to leave out
+    pub exclude: Option<Gitignore>,
+    /// what `exclude` was built from, the exclude file's lines included,
+    /// so a cache made with other patterns isn't used
+    pub exclude_patterns: Vec<String>,
+    /// count what symlinks point to, each file and directory is still only counted once
+    pub follow_symlinks: bool,
+}
+
+pub fn build_exclude<'a, I>(
+    root_path: &Path,
+    patterns: I,
+    exclude_file: Option<&Path>,
+) -> Result<Option<Gitignore>, Error>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut builder = GitignoreBuilder::new(root_path);
+
+    if let Some(exclude_file) = exclude_file {
+        if let Some(err) = builder.add(exclude_file) {
+            return Err(err.into());
+        }
+    }
+
+    for pattern in patterns {
+        builder.add_line(None, pattern)?;
+    }
+
+    let exclude = builder.build()?;
+    Ok(if exclude.is_empty() {
+        None
+    } else {
+        Some(exclude)
+    })
+}
+
+#[derive(Debug, Default)]
+struct EntryState {
+    metadata: Option<Result<Metadata, jwalk::Error>>,
+    skipped: Option<SkipReason>,
+    /// metadata of the target of a symlink we're following
+    followed: Option<Metadata>,
+}
+
+/// (device, inode) of every directory walked so far,
+/// so following a symlink can't take us around in circles
+type SeenDirs = Arc<Mutex<HashSet<(u64, u64)>>>;
+
+// (device, inode) of files with more than one link,
+// other files can't be seen twice so we don't remember them
+#[cfg(unix)]
+fn hard_link_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    if metadata.nlink() > 1 {
+        Some((metadata.dev(), metadata.ino()))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn inode_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// without inodes we can't tell where a loop is, so never follow into directories
+#[cfg(not(unix))]
+fn inode_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn device_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+// sparse and compressed files use less than their length
+#[cfg(unix)]
+pub fn allocated_size(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.blocks() * 512
 }
 
-pub fn walk(root_path: PathBuf) -> impl Iterator<Item = FileType> {
+#[cfg(not(unix))]
+pub fn allocated_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn owner(metadata: &Metadata) -> (Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+
+    (Some(metadata.uid()), Some(metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_metadata: &Metadata) -> (Option<u32>, Option<u32>) {
+    (None, None)
+}
+
+// without jwalk's "IO error for operation on <path>:" since we show the path already
+fn error_message(err: &jwalk::Error) -> String {
+    err.io_error()
+        .map_or_else(|| err.to_string(), ToString::to_string)
+}
+
+/// seconds since the unix epoch
+pub fn unix_time(time: io::Result<SystemTime>) -> Option<u64> {
+    time.ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_secs())
+}
+
+fn dir_skip_reason(
+    path: &Path,
+    metadata: Option<&Metadata>,
+    root_device: Option<u64>,
+    exclude: Option<&Gitignore>,
+) -> Option<SkipReason> {
+    if let (Some(root_device), Some(metadata)) = (root_device, metadata) {
+        if device_id(metadata) != Some(root_device) {
+            return Some(SkipReason::MountPoint);
+        }
+    }
+
+    if let Some(exclude) = exclude {
+        if exclude.matched(path, true).is_ignore() {
+            return Some(SkipReason::Excluded);
+        }
+    }
+
+    None
+}
+
+/// Why a walk of `root_path` wouldn't go into `path` somewhere below it. Walks
+/// only check what's inside where they start, so a rescan has to ask first.
+/// Files can only be `Excluded`.
+pub fn skip_reason_below(
+    root_path: &Path,
+    path: &Path,
+    metadata: &Metadata,
+    options: &WalkOptions,
+) -> Option<SkipReason> {
+    if !metadata.is_dir() {
+        return options
+            .exclude
+            .as_ref()
+            .filter(|exclude| exclude.matched(path, false).is_ignore())
+            .map(|_| SkipReason::Excluded);
+    }
+
+    let root_device = if options.one_file_system {
+        fs::metadata(root_path)
+            .ok()
+            .and_then(|metadata| device_id(&metadata))
+    } else {
+        None
+    };
+
+    dir_skip_reason(path, Some(metadata), root_device, options.exclude.as_ref())
+}
+
+// Returns the target's metadata if the link should be followed,
+// for directories only the first time we get there.
+fn follow_symlink(path: &Path, seen_dirs: &SeenDirs) -> Option<Metadata> {
+    let metadata = fs::metadata(path).ok()?;
+
+    if metadata.is_dir() {
+        let id = inode_id(&metadata)?;
+        if !seen_dirs.lock().unwrap().insert(id) {
+            return None;
+        }
+    }
+
+    Some(metadata)
+}
+
+fn new_seen_dirs(root_path: &Path) -> SeenDirs {
+    let seen_dirs = SeenDirs::default();
+    if let Some(id) = fs::metadata(root_path)
+        .ok()
+        .and_then(|metadata| inode_id(&metadata))
+    {
+        seen_dirs.lock().unwrap().insert(id);
+    }
+    seen_dirs
+}
+
+// process_children doesn't see the root, which is only a file for rescans
+fn entry_metadata(entry: &mut DirEntry<((), EntryState)>) -> Result<Metadata, jwalk::Error> {
+    entry
+        .client_state
+        .metadata
+        .take()
+        .unwrap_or_else(|| entry.metadata())
+}
+
+fn dir_modified(
+    entry: &mut DirEntry<((), EntryState)>,
+    followed: Option<&Metadata>,
+) -> Option<u64> {
+    let modified = match followed {
+        Some(metadata) => metadata.modified(),
+        None => entry_metadata(entry).ok()?.modified(),
+    };
+
+    unix_time(modified)
+}
+
+fn unfollowed_symlink(path: PathBuf, entry: &mut DirEntry<((), EntryState)>) -> FileType {
+    match (entry_metadata(entry), fs::read_link(entry.path())) {
+        (Ok(metadata), Ok(target)) => FileType::Symlink(FileSize::new(path, &metadata), target),
+        (Err(err), _) => FileType::Error(path, error_message(&err)),
+        (_, Err(err)) => FileType::Error(path, err.to_string()),
+    }
+}
+
+// a file, or another link to one that was already counted
+fn walked_file(
+    path: PathBuf,
+    metadata: &Metadata,
+    follow_symlinks: bool,
+    seen_hard_links: &mut HashSet<(u64, u64)>,
+) -> FileType {
+    let mut file_size = FileSize::new(path, metadata);
+    if follow_symlinks {
+        // links and what they point to can be anywhere
+        file_size.inode = inode_id(metadata);
+    }
+
+    match file_size.inode {
+        Some(id) if !seen_hard_links.insert(id) => FileType::HardLink(file_size),
+        _ => FileType::File(file_size),
+    }
+}
+
+// runs on jwalk's threads, before entries come out of the iterator
+fn process_children(
+    children: &mut Vec<jwalk::Result<DirEntry<((), EntryState)>>>,
+    root_device: Option<u64>,
+    exclude: Option<&Gitignore>,
+    seen_dirs: Option<&SeenDirs>,
+) {
+    if let Some(exclude) = exclude {
+        // excluded files are dropped, excluded dirs are kept below
+        // so they can be shown
+        children.retain(|dir_entry_result| match dir_entry_result {
+            Ok(dir_entry) => {
+                dir_entry.file_type.is_dir()
+                    || !exclude.matched(dir_entry.path(), false).is_ignore()
+            }
+            Err(_) => true,
+        });
+    }
+
+    children.iter_mut().for_each(|dir_entry_result| {
+        if let Ok(dir_entry) = dir_entry_result {
+            let metadata = dir_entry.metadata();
+            let path = dir_entry.path();
+
+            if let (true, Some(seen_dirs)) = (dir_entry.file_type.is_symlink(), seen_dirs) {
+                dir_entry.client_state.followed = follow_symlink(&path, seen_dirs);
+            }
+
+            let dir_metadata = if dir_entry.file_type.is_dir() {
+                metadata.as_ref().ok()
+            } else {
+                dir_entry
+                    .client_state
+                    .followed
+                    .as_ref()
+                    .filter(|followed| followed.is_dir())
+            };
+
+            if dir_entry.file_type.is_dir() || dir_metadata.is_some() {
+                // followed symlinks are already in there, a directory we got to
+                // through one first is left empty so it's only counted once
+                // (the root is put in first, and can come through here too)
+                let walked_before = match (seen_dirs, dir_metadata.and_then(inode_id)) {
+                    (Some(seen_dirs), Some(id))
+                        if dir_entry.file_type.is_dir() && dir_entry.depth > 0 =>
+                    {
+                        !seen_dirs.lock().unwrap().insert(id)
+                    }
+                    _ => false,
+                };
+
+                let skipped = dir_skip_reason(&path, dir_metadata, root_device, exclude);
+                dir_entry.read_children_path = if skipped.is_some() || walked_before {
+                    None
+                } else {
+                    Some(Arc::from(path))
+                };
+                dir_entry.client_state.skipped = skipped;
+            }
+
+            dir_entry.client_state.metadata = Some(metadata);
+        }
+    });
+}
+
+pub fn walk(root_path: PathBuf, options: &WalkOptions) -> impl Iterator<Item = FileType> {
+    walk_to_depth(root_path, options, usize::MAX, HashSet::new())
+}
+
+/// Walk part of a tree again, files in `counted` are somewhere else in it
+/// so they only come out as `HardLink`s.
+pub fn walk_part(
+    root_path: PathBuf,
+    options: &WalkOptions,
+    counted: HashSet<(u64, u64)>,
+) -> impl Iterator<Item = FileType> {
+    walk_to_depth(root_path, options, usize::MAX, counted)
+}
+
+/// Only `root_path` and what's directly inside, without going into directories,
+/// `counted` like for `walk_part`.
+pub fn walk_directory(
+    root_path: PathBuf,
+    options: &WalkOptions,
+    counted: HashSet<(u64, u64)>,
+) -> impl Iterator<Item = FileType> {
+    walk_to_depth(root_path, options, 1, counted)
+}
+
+fn walk_to_depth(
+    root_path: PathBuf,
+    options: &WalkOptions,
+    max_depth: usize,
+    mut seen_hard_links: HashSet<(u64, u64)>,
+) -> impl Iterator<Item = FileType> {
     info!("scanning {:?}", root_path);
 
+    let root_device = if options.one_file_system {
+        fs::metadata(&root_path)
+            .ok()
+            .and_then(|metadata| device_id(&metadata))
+    } else {
+        None
+    };
+
     // Rc<RefCell<>> causes a compiler panic :(
     let current_dirs = Arc::new(Mutex::new(Vec::new()));
     let current_dirs2 = current_dirs.clone();
 
-    WalkDirGeneric::<((), Option<Result<Metadata, jwalk::Error>>)>::new(&root_path)
+    let exclude = options.exclude.clone();
+
+    let follow_symlinks = options.follow_symlinks;
+    let seen_dirs = if follow_symlinks {
+        Some(new_seen_dirs(&root_path))
+    } else {
+        None
+    };
+
+    WalkDirGeneric::<((), EntryState)>::new(&root_path)
         .skip_hidden(false)
         .sort(false)
-        .process_read_dir(|_depth, _path, _read_dir_state, children| {
-            children.iter_mut().for_each(|dir_entry_result| {
-                if let Ok(dir_entry) = dir_entry_result {
-                    dir_entry.client_state = Some(dir_entry.metadata());
-                }
-            })
+        .max_depth(max_depth)
+        .process_read_dir(move |_depth, _path, _read_dir_state, children| {
+            process_children(children, root_device, exclude.as_ref(), seen_dirs.as_ref());
         })
         .into_iter()
         .filter_map(move |maybe_entry| {
             let mut current_dirs = current_dirs.lock().unwrap();
 
-            let entry = maybe_entry.ok()?;
-
-            let file_type = entry.file_type;
+            let (path, maybe_entry) = match maybe_entry {
+                Ok(entry) => (entry.path(), Ok(entry)),
+                // errors without a path aren't about any entry we could show
+                Err(err) => (err.path()?.to_path_buf(), Err(error_message(&err))),
+            };
 
             // for inputs like "." or ".." or "/"
             // the first entry.path() is "/"
-            let path = entry.path();
-            let path = if path.to_str().unwrap() == "/" {
+            let path = if path == Path::new("/") {
                 PathBuf::new()
             } else {
-                path.strip_prefix(&root_path).unwrap().to_path_buf()
+                path.strip_prefix(&root_path).ok()?.to_path_buf()
             };
 
             let mut out = Vec::new();
@@ -71,16 +524,44 @@ pub fn walk(root_path: PathBuf) -> impl Iterator<Item = FileType> {
                 }
             }
 
-            if file_type.is_dir() {
-                current_dirs.push(path.clone());
-                out.push(FileType::Dir(path, DirStatus::Started))
-            } else if file_type.is_file() {
-                let metadata = entry.client_state.unwrap().ok()?;
-                let len = metadata.len();
+            let mut entry = match maybe_entry {
+                Ok(entry) => entry,
+                Err(message) => {
+                    out.push(FileType::Error(path, message));
+                    return Some(out);
+                }
+            };
 
-                out.push(FileType::File(FileSize(path, len)))
+            let followed = entry.client_state.followed.take();
+            let (is_dir, is_file) = match &followed {
+                Some(metadata) => (metadata.is_dir(), metadata.is_file()),
+                None => (entry.file_type.is_dir(), entry.file_type.is_file()),
+            };
+
+            if let Some(reason) = entry.client_state.skipped {
+                out.push(FileType::Skipped(path, reason));
+            } else if is_dir {
+                let modified = dir_modified(&mut entry, followed.as_ref());
+
+                current_dirs.push(path.clone());
+                out.push(FileType::Dir(path.clone(), DirStatus::Started(modified)));
+                if let Some(err) = &entry.read_children_error {
+                    out.push(FileType::Error(path, error_message(err)));
+                }
+            } else if is_file {
+                out.push(
+                    match followed.map_or_else(|| entry_metadata(&mut entry), Ok) {
+                        Ok(metadata) => {
+                            walked_file(path, &metadata, follow_symlinks, &mut seen_hard_links)
+                        }
+                        Err(err) => FileType::Error(path, error_message(&err)),
+                    },
+                );
+            } else if entry.file_type.is_symlink() {
+                // broken, pointing at something we've seen, or not following
+                out.push(unfollowed_symlink(path, &mut entry));
             } else {
-                // None
+                out.push(FileType::Special(path, entry.file_type.into()));
             }
 
             Some(out)
@@ -99,9 +580,232 @@ pub fn walk(root_path: PathBuf) -> impl Iterator<Item = FileType> {
 fn test_walk() {
     crate::logger::initialize(true, false);
 
-    for item in walk("test-folder".parse().unwrap()) {
+    for item in walk("test-folder".parse().unwrap(), &WalkOptions::default()) {
         if let FileType::Dir(..) = item {
             println!("{:?}", item);
         }
     }
 }
+
+#[cfg(unix)]
+#[test]
+fn test_walk_hard_links() {
+    use std::fs;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("a"), "hello").unwrap();
+    fs::hard_link(root.join("a"), root.join("b")).unwrap();
+
+    let items: Vec<_> = walk(root.to_path_buf(), &WalkOptions::default()).collect();
+
+    let files = items
+        .iter()
+        .filter(|item| matches!(item, FileType::File(_)))
+        .count();
+    let hard_links = items
+        .iter()
+        .filter(|item| matches!(item, FileType::HardLink(_)))
+        .count();
+    assert_eq!((files, hard_links), (1, 1));
+}
+
+#[test]
+fn test_walk_exclude() {
+    let root_path: PathBuf = "test-folder".parse().unwrap();
+    let exclude = build_exclude(&root_path, vec!["f12", "a1.txt"], None).unwrap();
+    let options = WalkOptions {
+        exclude,
+        ..WalkOptions::default()
+    };
+
+    let items: Vec<_> = walk(root_path, &options).collect();
+
+    assert!(items.iter().any(|item| matches!(
+        item,
+        FileType::Skipped(path, SkipReason::Excluded) if path == Path::new("f1/f12")
+    )));
+    assert!(!items.iter().any(|item| matches!(
+        item,
+        FileType::File(FileSize { path, .. }) if path.starts_with("f1/f12") || path == Path::new("a1.txt")
+    )));
+}
+
+#[test]
+fn test_skip_reason_below() {
+    let root_path: PathBuf = "test-folder".parse().unwrap();
+    let exclude = build_exclude(&root_path, vec!["f12", "a1.txt"], None).unwrap();
+    let options = WalkOptions {
+        exclude,
+        ..WalkOptions::default()
+    };
+
+    let skip_reason = |path: &str| {
+        let path = root_path.join(path);
+        skip_reason_below(&root_path, &path, &fs::metadata(&path).unwrap(), &options)
+    };
+
+    assert!(matches!(skip_reason("f1/f12"), Some(SkipReason::Excluded)));
+    assert!(matches!(skip_reason("a1.txt"), Some(SkipReason::Excluded)));
+    assert!(skip_reason("f1/f11").is_none());
+    assert!(skip_reason("f1/a11.txt").is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_errors() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("locked")).unwrap();
+    fs::write(root.join("locked/secret"), "hello").unwrap();
+    fs::set_permissions(root.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+
+    // root can read anything
+    let readable = fs::read_dir(root.join("locked")).is_ok();
+
+    let items: Vec<_> = walk(root.to_path_buf(), &WalkOptions::default()).collect();
+    fs::set_permissions(root.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    let locked_error = items.iter().any(|item| {
+        matches!(
+            item,
+            FileType::Error(path, _) if path == Path::new("locked")
+        )
+    });
+    let secret_found = items.iter().any(|item| {
+        matches!(
+            item,
+            FileType::File(FileSize { path, .. }) if path == Path::new("locked/secret")
+        )
+    });
+    // an error where it can't be read, its contents where it can
+    assert_eq!((locked_error, secret_found), (!readable, readable));
+
+    // fails even for root
+    let missing: Vec<_> = walk(root.join("missing"), &WalkOptions::default()).collect();
+    assert!(matches!(
+        missing.as_slice(),
+        [FileType::Error(path, _)] if path == Path::new("")
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_symlinks() {
+    use std::{fs, os::unix::fs::symlink};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("dir")).unwrap();
+    fs::write(root.join("dir/file"), "hello").unwrap();
+    symlink("dir/file", root.join("file-link")).unwrap();
+    // a loop back to the root
+    symlink("..", root.join("dir/parent")).unwrap();
+
+    let unfollowed: Vec<_> = walk(root.to_path_buf(), &WalkOptions::default()).collect();
+    let followed: Vec<_> = walk(
+        root.to_path_buf(),
+        &WalkOptions {
+            follow_symlinks: true,
+            ..WalkOptions::default()
+        },
+    )
+    .collect();
+
+    let symlinks = |items: &[FileType]| {
+        items
+            .iter()
+            .filter(|item| matches!(item, FileType::Symlink(..)))
+            .count()
+    };
+    let files = |items: &[FileType]| {
+        items
+            .iter()
+            .filter(|item| matches!(item, FileType::File(_)))
+            .count()
+    };
+    let hard_links = |items: &[FileType]| {
+        items
+            .iter()
+            .filter(|item| matches!(item, FileType::HardLink(_)))
+            .count()
+    };
+
+    assert_eq!((files(&unfollowed), symlinks(&unfollowed)), (1, 2));
+    assert!(unfollowed.iter().any(|item| matches!(
+        item,
+        FileType::Symlink(_, target) if target == Path::new("dir/file")
+    )));
+
+    // the file and the link to it only count once, the loop is left as a link
+    assert_eq!(
+        (files(&followed), hard_links(&followed), symlinks(&followed)),
+        (1, 1, 1)
+    );
+    // the file itself only has the one link
+    assert!(followed.iter().all(|item| !matches!(
+        item,
+        FileType::File(FileSize {
+            multiple_links: true,
+            ..
+        })
+    )));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_symlinked_dir() {
+    use std::{fs, os::unix::fs::symlink};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("dir")).unwrap();
+    fs::write(root.join("dir/file"), "hello").unwrap();
+    symlink("dir", root.join("dir-link")).unwrap();
+
+    let mut tree = super::Directory::new();
+    for file_type in walk(
+        root.to_path_buf(),
+        &WalkOptions {
+            follow_symlinks: true,
+            ..WalkOptions::default()
+        },
+    ) {
+        tree.update(&file_type);
+    }
+
+    // through whichever of the two was walked first
+    assert_eq!(
+        (tree.file_count, tree.hard_links, tree.total_size),
+        (1, 0, 5)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_walk_special() {
+    use std::os::unix::net::UnixListener;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    let _listener = UnixListener::bind(root.join("socket")).unwrap();
+
+    let items: Vec<_> = walk(root.to_path_buf(), &WalkOptions::default()).collect();
+
+    assert!(items.iter().any(|item| matches!(
+        item,
+        FileType::Special(path, SpecialKind::Socket) if path == Path::new("socket")
+    )));
+}
+
+#[test]
+fn test_walk_file() {
+    let items: Vec<_> = walk("test-folder/a1.txt".into(), &WalkOptions::default()).collect();
+
+    assert!(matches!(
+        items.as_slice(),
+        [FileType::File(FileSize { path, .. })] if path == Path::new("")
+    ));
+}
@@ -0,0 +1,193 @@
+//! Keeps a finished scan current by telling the scanner what changed on disk.
+
+use super::Roots;
+use log::{debug, info};
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// changes are collected this long so a file being written is only rescanned once
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// paths in the tree, none inside another
+    Changed(Vec<PathBuf>),
+    /// the watcher stopped or missed something
+    Error(String),
+}
+
+/// Each walked directory is watched on its own, so nothing is watched
+/// inside excluded ones or on other filesystems.
+/// Changes stop coming once this is dropped.
+pub struct DirWatcher {
+    roots: Roots,
+    watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+impl DirWatcher {
+    /// Also watch a directory walked since, watching one again does nothing.
+    pub fn watch(&self, path: &[String]) {
+        if let Some(full_path) = self.roots.full_path(path) {
+            if let Err(e) = self
+                .watcher
+                .lock()
+                .unwrap()
+                .watch(&full_path, RecursiveMode::NonRecursive)
+            {
+                debug!("couldn't watch {:?}: {}", full_path, e);
+            }
+        }
+    }
+}
+
+/// Watch `directories` in the tree, sending what changed from
+/// another thread until `send_event` returns false.
+pub fn spawn_watcher<F>(
+    roots: &Roots,
+    directories: Vec<Vec<String>>,
+    mut send_event: F,
+) -> Option<DirWatcher>
+where
+    F: FnMut(WatchEvent) -> bool + Send + 'static,
+{
+    // events come back with the real path on some platforms
+    let roots = roots.canonicalize();
+
+    let (sender, receiver) = channel();
+    let watcher = match notify::recommended_watcher(sender) {
+        Ok(watcher) => Arc::new(Mutex::new(watcher)),
+        Err(e) => {
+            send_event(WatchEvent::Error(format!(
+                "couldn't watch for changes: {}",
+                e
+            )));
+            return None;
+        }
+    };
+
+    let dir_watcher = DirWatcher {
+        roots: roots.clone(),
+        watcher: watcher.clone(),
+    };
+
+    thread::spawn(move || {
+        // a watch for every directory so it can take a while
+        info!("watching {} directories", directories.len());
+        let mut failed = 0;
+        let mut first_error = None;
+        for path in &directories {
+            if let Some(full_path) = roots.full_path(path) {
+                // locked for each one so DirWatcher::watch doesn't wait for all of them
+                let result = watcher
+                    .lock()
+                    .unwrap()
+                    .watch(&full_path, RecursiveMode::NonRecursive);
+                if let Err(e) = result {
+                    failed += 1;
+                    first_error.get_or_insert_with(|| format!("{:?}: {}", full_path, e));
+                }
+            }
+        }
+        // only the DirWatcher keeps it now
+        drop(watcher);
+        debug!("watching ready");
+
+        if let Some(first_error) = first_error {
+            let message = format!(
+                "couldn't watch {} directories for changes, the first {}",
+                failed, first_error
+            );
+            if !send_event(WatchEvent::Error(message)) {
+                return;
+            }
+        }
+
+        while let Ok(first) = receiver.recv() {
+            let mut changed = BTreeSet::new();
+            let mut errors = Vec::new();
+            let mut add = |result: notify::Result<Event>| match result {
+                Ok(event) => changed.extend(changed_paths(&roots, event)),
+                Err(e) => errors.push(e.to_string()),
+            };
+            add(first);
+
+            let deadline = Instant::now() + WATCH_INTERVAL;
+            loop {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(result) => add(result),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            for message in errors {
+                if !send_event(WatchEvent::Error(message)) {
+                    return;
+                }
+            }
+
+            let changed = outermost(changed);
+            if !changed.is_empty() && !send_event(WatchEvent::Changed(changed)) {
+                return;
+            }
+        }
+    });
+
+    Some(dir_watcher)
+}
+
+/// Paths in the tree that could have changed size.
+fn changed_paths(roots: &Roots, event: Event) -> Vec<PathBuf> {
+    match event.kind {
+        // reads and permission changes don't change any size
+        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => Vec::new(),
+
+        // for renames "from" is gone and "to" is new, both need looking at
+        _ => event
+            .paths
+            .into_iter()
+            .filter_map(|path| Some(roots.tree_path(&path)?.iter().collect()))
+            .collect(),
+    }
+}
+
+/// Drop paths inside other ones, rescanning the outer one covers them.
+fn outermost(paths: BTreeSet<PathBuf>) -> Vec<PathBuf> {
+    let mut outermost: Vec<PathBuf> = Vec::new();
+
+    // sorted, so a directory comes right before what's inside it
+    for path in paths {
+        match outermost.last() {
+            Some(last) if path.starts_with(last) => {}
+            _ => outermost.push(path),
+        }
+    }
+
+    outermost
+}
+
+#[test]
+fn test_outermost() {
+    let paths = vec!["a/b", "a", "a/c/d", "b/c", "ab"]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+
+    assert_eq!(
+        outermost(paths),
+        vec![
+            PathBuf::from("a"),
+            PathBuf::from("ab"),
+            PathBuf::from("b/c")
+        ]
+    );
+}
@@ -0,0 +1,86 @@
+import React from "react";
+import {
+  Drawer,
+  HTMLTable,
+  Classes,
+  NonIdealState,
+  Spinner,
+} from "@blueprintjs/core";
+import { bytes } from "./helpers";
+
+const PathColumnStyle = { width: "100%", cursor: "pointer" };
+const SizeColumnStyle: { textAlign: "right" } = { textAlign: "right" };
+
+interface DuplicatesViewProps {
+  isOpen: boolean;
+  /** null while files are being hashed */
+  duplicates: EventMessageDuplicates | null;
+  onClose: () => void;
+  /** open the folder containing a file */
+  onChangeDirectory: (path: Path) => void;
+}
+
+export default class DuplicatesView extends React.Component<
+  DuplicatesViewProps,
+  {}
+> {
+  render() {
+    const { isOpen, duplicates, onClose, onChangeDirectory } = this.props;
+
+    return (
+      <Drawer
+        isOpen={isOpen}
+        onClose={onClose}
+        icon="duplicate"
+        title={
+          duplicates != null
+            ? `Duplicate files (${bytes(duplicates.reclaimableSize)} ` +
+              "reclaimable)"
+            : "Duplicate files"
+        }
+        size={Drawer.SIZE_LARGE}
+      >
+        <div className={Classes.DRAWER_BODY}>
+          {duplicates == null ? (
+            <NonIdealState icon={<Spinner />} title="Comparing files" />
+          ) : duplicates.groups.length === 0 ? (
+            <NonIdealState icon="tick" title="No duplicates found" />
+          ) : (
+            <HTMLTable interactive condensed style={{ width: "100%" }}>
+              {duplicates.groups.map(({ size, paths }, i) => {
+                const reclaimable = size * (paths.length - 1);
+
+                return (
+                  <tbody key={i}>
+                    <tr>
+                      <th>{`${paths.length} copies of ${bytes(size)}`}</th>
+                      <th
+                        style={SizeColumnStyle}
+                        title={`${reclaimable.toLocaleString()} bytes`}
+                      >
+                        {bytes(reclaimable)}
+                      </th>
+                    </tr>
+                    {paths.map((path) => (
+                      <tr
+                        key={path.join("/")}
+                        title="Open containing folder"
+                        onClick={() => {
+                          onChangeDirectory(path.slice(0, -1));
+                        }}
+                      >
+                        <td style={PathColumnStyle} colSpan={2}>
+                          {path.join("/")}
+                        </td>
+                      </tr>
+                    ))}
+                  </tbody>
+                );
+              })}
+            </HTMLTable>
+          )}
+        </div>
+      </Drawer>
+    );
+  }
+}
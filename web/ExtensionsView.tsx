@@ -0,0 +1,57 @@
+import React from "react";
+import { Card, H5, HTMLTable } from "@blueprintjs/core";
+import { bytes, SizeKind } from "./helpers";
+
+const SizeColumnStyle: { textAlign: "right" } = { textAlign: "right" };
+
+interface ExtensionsViewProps {
+  /** null until the first breakdown arrives */
+  extensions: Array<ExtensionSize> | null;
+  sizeKind: SizeKind;
+}
+
+export default class ExtensionsView extends React.Component<
+  ExtensionsViewProps,
+  {}
+> {
+  render() {
+    const { extensions, sizeKind } = this.props;
+
+    const sorted = (extensions || [])
+      .slice(0)
+      .sort((left, right) => right[sizeKind] - left[sizeKind]);
+
+    const totalSize = sorted
+      .map((extension) => extension[sizeKind])
+      .reduce((last, current) => last + current, 0);
+
+    return (
+      <Card style={{ minWidth: "280px" }}>
+        <H5>File types</H5>
+        <HTMLTable condensed style={{ width: "100%" }}>
+          <tbody>
+            {sorted.map(({ extension, fileCount, ...sizes }) => {
+              const size = sizes[sizeKind];
+              const percent = totalSize > 0 ? (size / totalSize) * 100 : 0;
+
+              return (
+                <tr key={extension}>
+                  <td title={`${fileCount.toLocaleString()} files`}>
+                    {extension !== "" ? `.${extension}` : "(none)"}
+                  </td>
+                  <td style={SizeColumnStyle}>{`${percent.toFixed(1)}%`}</td>
+                  <td
+                    style={SizeColumnStyle}
+                    title={`${size.toLocaleString()} bytes`}
+                  >
+                    {bytes(size)}
+                  </td>
+                </tr>
+              );
+            })}
+          </tbody>
+        </HTMLTable>
+      </Card>
+    );
+  }
+}
@@ -9,7 +9,21 @@ import {
   MenuItem,
   Spinner,
 } from "@blueprintjs/core";
-import { bytes, time } from "./helpers";
+import {
+  age,
+  AGE_BUCKETS,
+  bytes,
+  bytesChange,
+  daysAgo,
+  entryCount,
+  entryLastTouched,
+  entrySize,
+  entrySizeChange,
+  SizeKind,
+  SortBy,
+  SPECIAL_KINDS,
+  time,
+} from "./helpers";
 import ReactDOM from "react-dom";
 
 class ProgressBar extends React.Component<
@@ -64,20 +78,27 @@ class EntryRow extends React.Component<
     onReveal: () => void;
     onClick: () => void;
     totalSize: number;
+    sizeKind: SizeKind;
+    readOnly: boolean;
+    showChange: boolean;
+    showCount: boolean;
+    showAge: boolean;
   },
   {}
 > {
   public renderContextMenu() {
-    const { onDelete, onReveal } = this.props;
+    const { onDelete, onReveal, readOnly } = this.props;
     return (
       <Menu>
         <MenuItem
+          disabled={readOnly}
           onClick={() => {
             onReveal();
           }}
           text="Reveal"
         />
         <MenuItem
+          disabled={readOnly}
           onClick={() => {
             onDelete();
           }}
@@ -88,7 +109,18 @@ class EntryRow extends React.Component<
   }
 
   render() {
-    const { entry, onClick, totalSize } = this.props;
+    const {
+      entry,
+      onClick,
+      totalSize,
+      sizeKind,
+      showChange,
+      showCount,
+      showAge,
+    } = this.props;
+    const size = entrySize(entry, sizeKind);
+    const sizeChange = entrySizeChange(entry);
+    const lastTouched = entryLastTouched(entry);
 
     return (
       <tr
@@ -97,7 +129,7 @@ class EntryRow extends React.Component<
         }}
       >
         <td style={NameColumnStyle}>
-          <ProgressBar value={entry.size / totalSize}>
+          <ProgressBar value={size / totalSize}>
             <div
               style={{
                 paddingRight: "10px",
@@ -111,8 +143,22 @@ class EntryRow extends React.Component<
                 ) : entry.updating === "updating" ? (
                   <Spinner size={20} intent="success" />
                 ) : entry.updating === "finished" ? (
-                  <Icon iconSize={20} intent="primary" icon="folder-close" />
+                  <Icon
+                    iconSize={20}
+                    intent={entry.unreadableCount > 0 ? "warning" : "primary"}
+                    icon="folder-close"
+                  />
                 ) : null
+              ) : entry.type === "mountPoint" ? (
+                <Icon iconSize={20} icon="database" />
+              ) : entry.type === "excluded" ? (
+                <Icon iconSize={20} icon="disable" />
+              ) : entry.type === "error" ? (
+                <Icon iconSize={20} intent="danger" icon="error" />
+              ) : entry.type === "symlink" ? (
+                <Icon iconSize={20} icon="link" />
+              ) : entry.type === "special" ? (
+                <Icon iconSize={20} icon="cog" />
               ) : (
                 <Icon iconSize={20} intent="primary" icon="document" />
               )}
@@ -122,10 +168,84 @@ class EntryRow extends React.Component<
         </td>
         <td
           style={SizeColumnStyle}
-          title={`${entry.size.toLocaleString()} bytes`}
+          title={
+            `${size.toLocaleString()} bytes` +
+            (entry.type === "directory"
+              ? `\n${entry.fileCount.toLocaleString()} files, ` +
+                `${entry.dirCount.toLocaleString()} folders`
+              : "") +
+            (entry.type === "error" ? `\n${entry.message}` : "") +
+            (entry.type === "symlink"
+              ? `\nlink to ${entry.target}, ` +
+                `${entry.size.toLocaleString()} bytes not counted`
+              : "") +
+            (entry.type === "directory" && entry.error != null
+              ? `\ncouldn't be listed: ${entry.error}`
+              : "") +
+            (entry.type === "directory" && entry.unreadableCount > 0
+              ? `\n${entry.unreadableCount.toLocaleString()} ` +
+                "files or folders couldn't be read, so this is too low"
+              : "") +
+            (entry.type === "directory" && entry.hardLinks > 0
+              ? `\n${entry.hardLinks.toLocaleString()} hard links, ` +
+                `${bytes(entry.hardLinksSize)} not counted twice`
+              : "") +
+            (entry.type === "directory" && lastTouched != null
+              ? "\nlast used:" +
+                entry.ageHistogram
+                  .map(
+                    (bucketSize, i) =>
+                      `\n  ${AGE_BUCKETS[i]}: ${bytes(bucketSize)}`
+                  )
+                  .join("")
+              : "")
+          }
         >
-          {bytes(entry.size)}
+          {entry.type === "mountPoint"
+            ? "mount point"
+            : entry.type === "excluded"
+            ? "excluded"
+            : entry.type === "error"
+            ? "unreadable"
+            : entry.type === "symlink"
+            ? `\u2192 ${entry.target}`
+            : entry.type === "special"
+            ? SPECIAL_KINDS[entry.kind]
+            : entry.type === "directory" && entry.unreadableCount > 0
+            ? `\u2265 ${bytes(size)}`
+            : bytes(size)}
         </td>
+        {showCount ? (
+          <td style={SizeColumnStyle}>
+            {entry.type === "directory"
+              ? entryCount(entry).toLocaleString()
+              : null}
+          </td>
+        ) : null}
+        {showChange ? (
+          <td
+            style={SizeColumnStyle}
+            title={
+              sizeChange != null
+                ? `${sizeChange.toLocaleString()} bytes`
+                : undefined
+            }
+          >
+            {sizeChange != null ? bytesChange(sizeChange) : null}
+          </td>
+        ) : null}
+        {showAge ? (
+          <td
+            style={SizeColumnStyle}
+            title={
+              lastTouched != null
+                ? new Date(lastTouched * 1000).toLocaleString()
+                : undefined
+            }
+          >
+            {lastTouched != null ? age(lastTouched) : null}
+          </td>
+        ) : null}
       </tr>
     );
   }
@@ -133,6 +253,11 @@ class EntryRow extends React.Component<
 
 interface FolderViewProps {
   entries: Array<Entry>;
+  sizeKind: SizeKind;
+  sortBy: SortBy;
+  /** only show what wasn't modified or accessed in this many days */
+  staleDays: number | null;
+  readOnly: boolean;
   onChangeDirectory: (entry: Entry) => void;
   onDelete: (entry: Entry) => void;
   onReveal: (entry: Entry) => void;
@@ -186,8 +311,22 @@ export default class FolderView extends React.Component<
     return false;
   }
 
+  getShownEntries(): Array<Entry> {
+    const { entries, staleDays } = this.props;
+
+    if (staleDays == null) {
+      return entries;
+    }
+
+    const cutoff = daysAgo(staleDays);
+    return entries.filter((entry) => {
+      const lastTouched = entryLastTouched(entry);
+      return lastTouched != null && lastTouched < cutoff;
+    });
+  }
+
   handleScroll = () => {
-    const { entries } = this.props;
+    const entries = this.getShownEntries();
     const { numberOfShownEntries } = this.state;
 
     if (entries.length <= numberOfShownEntries) {
@@ -207,13 +346,29 @@ export default class FolderView extends React.Component<
 
   render() {
     return time("FolderView render", () => {
-      const { entries, onChangeDirectory, onDelete, onReveal } = this.props;
+      const {
+        sizeKind,
+        sortBy,
+        staleDays,
+        readOnly,
+        onChangeDirectory,
+        onDelete,
+        onReveal,
+      } = this.props;
       const { deleteEntry, numberOfShownEntries } = this.state;
 
+      const entries = this.getShownEntries();
+
       const totalSize = entries
-        .map((entry) => entry.size)
+        .map((entry) => entrySize(entry, sizeKind))
         .reduce((last, current) => last + current, 0);
 
+      const showChange = entries.some(
+        (entry) => entrySizeChange(entry) != null
+      );
+
+      const showAge = sortBy === "age" || staleDays != null;
+
       const sortedEntries = entries
         .slice(0)
         .sort((left, right) => {
@@ -225,9 +380,39 @@ export default class FolderView extends React.Component<
           if (isNotYetUpdated(left) && !isNotYetUpdated(right)) return -1;
           if (!isNotYetUpdated(left) && isNotYetUpdated(right)) return 1;
 
+          // most files first
+          if (sortBy === "files") {
+            const leftCount = entryCount(left);
+            const rightCount = entryCount(right);
+            if (leftCount > rightCount) return -1;
+            if (leftCount < rightCount) return 1;
+          }
+
+          // least recently used first, unknown last
+          if (sortBy === "age") {
+            const leftTouched = entryLastTouched(left);
+            const rightTouched = entryLastTouched(right);
+            if (leftTouched != null && rightTouched == null) return -1;
+            if (leftTouched == null && rightTouched != null) return 1;
+            if (leftTouched != null && rightTouched != null) {
+              if (leftTouched < rightTouched) return -1;
+              if (leftTouched > rightTouched) return 1;
+            }
+          }
+
+          // most growth first
+          if (sortBy === "change") {
+            const leftChange = entrySizeChange(left) || 0;
+            const rightChange = entrySizeChange(right) || 0;
+            if (leftChange > rightChange) return -1;
+            if (leftChange < rightChange) return 1;
+          }
+
           // greater first
-          if (left.size > right.size) return -1;
-          if (left.size < right.size) return 1;
+          const leftSize = entrySize(left, sizeKind);
+          const rightSize = entrySize(right, sizeKind);
+          if (leftSize > rightSize) return -1;
+          if (leftSize < rightSize) return 1;
 
           // show directories first
           if (left.type === "directory" && right.type === "file") return -1;
@@ -263,6 +448,11 @@ export default class FolderView extends React.Component<
             onReveal(entry);
           }}
           totalSize={totalSize}
+          sizeKind={sizeKind}
+          readOnly={readOnly}
+          showChange={showChange}
+          showCount={sortBy === "files"}
+          showAge={showAge}
         />
       ));
 
@@ -310,6 +500,11 @@ export default class FolderView extends React.Component<
               <tr>
                 <th style={NameColumnStyle}>Name</th>
                 <th style={SizeColumnStyle}>Size</th>
+                {sortBy === "files" ? (
+                  <th style={SizeColumnStyle}>Items</th>
+                ) : null}
+                {showChange ? <th style={SizeColumnStyle}>Change</th> : null}
+                {showAge ? <th style={SizeColumnStyle}>Last used</th> : null}
               </tr>
             </thead>
             <tbody>{sortedEntriesElements}</tbody>
@@ -0,0 +1,66 @@
+import React from "react";
+import { Drawer, HTMLTable, Classes, Spinner } from "@blueprintjs/core";
+import { bytes } from "./helpers";
+
+const PathColumnStyle = { width: "100%", cursor: "pointer" };
+const SizeColumnStyle: { textAlign: "right" } = { textAlign: "right" };
+
+interface LargestFilesViewProps {
+  isOpen: boolean;
+  /** largest first by apparent size, null until the first list arrives */
+  entries: Array<EntryFile> | null;
+  onClose: () => void;
+  /** open the folder containing a file */
+  onChangeDirectory: (path: Path) => void;
+}
+
+export default class LargestFilesView extends React.Component<
+  LargestFilesViewProps,
+  {}
+> {
+  render() {
+    const { isOpen, entries, onClose, onChangeDirectory } = this.props;
+
+    return (
+      <Drawer
+        isOpen={isOpen}
+        onClose={onClose}
+        icon="sort-numerical-desc"
+        title="Largest files"
+        size={Drawer.SIZE_LARGE}
+      >
+        <div className={Classes.DRAWER_BODY}>
+          {entries == null ? (
+            <Spinner />
+          ) : (
+            <HTMLTable interactive condensed style={{ width: "100%" }}>
+              <tbody>
+                {entries.map((entry) => {
+                  const { size } = entry;
+
+                  return (
+                    <tr
+                      key={entry.path.join("/")}
+                      title="Open containing folder"
+                      onClick={() => {
+                        onChangeDirectory(entry.path.slice(0, -1));
+                      }}
+                    >
+                      <td style={PathColumnStyle}>{entry.path.join("/")}</td>
+                      <td
+                        style={SizeColumnStyle}
+                        title={`${size.toLocaleString()} bytes`}
+                      >
+                        {bytes(size)}
+                      </td>
+                    </tr>
+                  );
+                })}
+              </tbody>
+            </HTMLTable>
+          )}
+        </div>
+      </Drawer>
+    );
+  }
+}
@@ -1,5 +1,10 @@
 import React from "react";
 import FolderView from "./FolderView";
+import LargestFilesView from "./LargestFilesView";
+import ExtensionsView from "./ExtensionsView";
+import OwnersView from "./OwnersView";
+import DuplicatesView from "./DuplicatesView";
+import VolumesView from "./VolumesView";
 import {
   Breadcrumbs,
   Divider,
@@ -10,8 +15,11 @@ import {
   Spinner,
   ProgressBar,
   Intent,
+  Switch,
+  HTMLSelect,
+  Button,
 } from "@blueprintjs/core";
-import { bytes, time } from "./helpers";
+import { bytes, entrySize, SizeKind, SortBy, time } from "./helpers";
 
 interface MainViewProps {
   ws: WebSocket;
@@ -22,9 +30,25 @@ interface MainViewState {
   currentDirectory?: EntryDirectory;
   entries: Array<Entry>;
   breadcrumbEntries: Array<Entry>;
-  availableSpace: number;
+  availableSpace: number | null;
+  sizeKind: SizeKind;
+  sortBy: SortBy;
+  staleDays: number | null;
+  readOnly: boolean;
+  showLargestFiles: boolean;
+  largestFiles: Array<EntryFile> | null;
+  showExtensions: boolean;
+  extensions: Array<ExtensionSize> | null;
+  showOwners: boolean;
+  owners: EventMessageOwners | null;
+  showDuplicates: boolean;
+  duplicates: EventMessageDuplicates | null;
+  showVolumes: boolean;
+  volumes: Array<Volume> | null;
 }
 
+const LARGEST_FILES_COUNT = 100;
+
 export default class MainView extends React.Component<
   MainViewProps,
   MainViewState
@@ -34,14 +58,25 @@ export default class MainView extends React.Component<
     entries: [],
     breadcrumbEntries: [],
     availableSpace: 0,
+    sizeKind: "size",
+    sortBy: "size",
+    staleDays: null,
+    readOnly: false,
+    showLargestFiles: false,
+    largestFiles: null,
+    showExtensions: false,
+    extensions: null,
+    showOwners: false,
+    owners: null,
+    showDuplicates: false,
+    duplicates: null,
+    showVolumes: false,
+    volumes: null,
   };
 
   componentDidMount() {
     const { ws } = this.props;
     ws.addEventListener("message", this.onMessage);
-
-    // get initial current directory entries
-    this.send({ type: "changeDirectory", path: [] });
   }
 
   componentWillUnmount() {
@@ -72,7 +107,30 @@ export default class MainView extends React.Component<
   };
 
   receive(data: EventMessage) {
-    if (data.type === "directoryChange") {
+    if (data.type === "sessionInfo") {
+      const { readOnly } = data;
+
+      // a new scan, maybe of another volume
+      this.setState({
+        currentDirectory: undefined,
+        entries: [],
+        breadcrumbEntries: [],
+        readOnly,
+        showLargestFiles: false,
+        largestFiles: null,
+        showExtensions: false,
+        extensions: null,
+        showOwners: false,
+        owners: null,
+        showDuplicates: false,
+        duplicates: null,
+        showVolumes: false,
+        volumes: null,
+      });
+
+      // get initial current directory entries
+      this.send({ type: "changeDirectory", path: [] });
+    } else if (data.type === "directoryChange") {
       const {
         currentDirectory,
         entries,
@@ -105,6 +163,34 @@ export default class MainView extends React.Component<
           }
         }),
       });
+    } else if (data.type === "largestFiles") {
+      const { entries } = data;
+
+      this.setState({ largestFiles: entries });
+    } else if (data.type === "extensions") {
+      const { path, extensions } = data;
+
+      const { currentDirectory } = this.state;
+      if (
+        currentDirectory &&
+        currentDirectory.path.join("/") === path.join("/")
+      ) {
+        this.setState({ extensions });
+      }
+    } else if (data.type === "owners") {
+      const { currentDirectory } = this.state;
+      if (
+        currentDirectory &&
+        currentDirectory.path.join("/") === data.path.join("/")
+      ) {
+        this.setState({ owners: data });
+      }
+    } else if (data.type === "duplicates") {
+      this.setState({ duplicates: data });
+    } else if (data.type === "volumes") {
+      const { volumes } = data;
+
+      this.setState({ volumes });
     } else if (data.type === "deleting") {
       const { toaster } = this.props;
 
@@ -135,6 +221,21 @@ export default class MainView extends React.Component<
           timeout: 3000,
         });
       }
+    } else if (data.type === "error") {
+      const { toaster } = this.props;
+      const { path, message } = data;
+
+      if (path) {
+        // stop showing a delete that won't finish
+        toaster.dismiss(path.join("/"));
+      }
+
+      toaster.show({
+        icon: "error",
+        message: path ? `${path.join("/")}: ${message}` : message,
+        intent: "danger",
+        timeout: 5000,
+      });
     }
   }
 
@@ -145,14 +246,46 @@ export default class MainView extends React.Component<
         entries,
         breadcrumbEntries,
         availableSpace,
+        sizeKind,
+        sortBy,
+        staleDays,
+        readOnly,
+        showLargestFiles,
+        largestFiles,
+        showExtensions,
+        extensions,
+        showOwners,
+        owners,
+        showDuplicates,
+        duplicates,
+        showVolumes,
+        volumes,
       } = this.state;
 
+      if (showVolumes || (!currentDirectory && volumes)) {
+        return (
+          <VolumesView
+            volumes={volumes}
+            onScan={(mountPoint) => {
+              this.send({ type: "scanVolume", mountPoint });
+            }}
+            onClose={
+              currentDirectory
+                ? () => {
+                    this.setState({ showVolumes: false });
+                  }
+                : undefined
+            }
+          />
+        );
+      }
+
       if (!currentDirectory) {
         return <div>loading</div>;
       }
 
       const totalSize = entries
-        .map((entry) => entry.size)
+        .map((entry) => entrySize(entry, sizeKind))
         .reduce((last, current) => last + current, 0);
 
       return (
@@ -206,22 +339,43 @@ export default class MainView extends React.Component<
             />
           </div>
 
-          <FolderView
-            key={"FolderView-" + currentDirectory.path.join("/")}
-            entries={entries}
-            onChangeDirectory={({ path }) => {
-              this.send({
-                type: "changeDirectory",
-                path,
-              });
-            }}
-            onDelete={({ path }) => {
-              this.send({ type: "delete", path });
-            }}
-            onReveal={({ path }) => {
-              this.send({ type: "reveal", path });
-            }}
-          />
+          <div style={{ display: "flex", alignItems: "flex-start" }}>
+            <div style={{ flexGrow: 1 }}>
+              <FolderView
+                key={"FolderView-" + currentDirectory.path.join("/")}
+                entries={entries}
+                sizeKind={sizeKind}
+                sortBy={sortBy}
+                staleDays={staleDays}
+                readOnly={readOnly}
+                onChangeDirectory={({ path }) => {
+                  this.send({
+                    type: "changeDirectory",
+                    path,
+                  });
+                }}
+                onDelete={({ path }) => {
+                  this.send({ type: "delete", path });
+                }}
+                onReveal={({ path }) => {
+                  this.send({ type: "reveal", path });
+                }}
+              />
+            </div>
+            {showExtensions || showOwners ? (
+              <div>
+                {showExtensions ? (
+                  <ExtensionsView
+                    extensions={extensions}
+                    sizeKind={sizeKind}
+                  />
+                ) : null}
+                {showOwners ? (
+                  <OwnersView owners={owners} sizeKind={sizeKind} />
+                ) : null}
+              </div>
+            ) : null}
+          </div>
 
           <div style={{ display: "flex" }}>
             <h4>{`${entries.length} items`}</h4>
@@ -229,11 +383,158 @@ export default class MainView extends React.Component<
             <h4 title={`${totalSize.toLocaleString()} bytes`}>
               {`Total size: ${bytes(totalSize)}`}
             </h4>
+            {availableSpace != null ? (
+              <>
+                <Divider />
+                <h4 title={`${availableSpace.toLocaleString()} bytes`}>
+                  {`Available space: ${bytes(availableSpace)}`}
+                </h4>
+              </>
+            ) : null}
             <Divider />
-            <h4 title={`${availableSpace.toLocaleString()} bytes`}>
-              {`Available space: ${bytes(availableSpace)}`}
-            </h4>
+            <Switch
+              style={{ margin: "auto 0" }}
+              checked={sizeKind === "allocatedSize"}
+              label="Size on disk"
+              onChange={() => {
+                this.setState({
+                  sizeKind: sizeKind === "size" ? "allocatedSize" : "size",
+                });
+              }}
+            />
+            <Divider />
+            <HTMLSelect
+              style={{ margin: "auto 0" }}
+              value={sortBy}
+              onChange={(event) => {
+                this.setState({
+                  sortBy: event.currentTarget.value as SortBy,
+                });
+              }}
+            >
+              <option value="size">Sort by size</option>
+              <option value="files">Sort by number of files</option>
+              <option value="age">Sort by least recently used</option>
+              {currentDirectory.previousSize != null ? (
+                <option value="change">Sort by growth</option>
+              ) : null}
+            </HTMLSelect>
+            <Divider />
+            <HTMLSelect
+              style={{ margin: "auto 0" }}
+              value={staleDays != null ? staleDays : ""}
+              onChange={(event) => {
+                const { value } = event.currentTarget;
+                this.setState({
+                  staleDays: value !== "" ? parseInt(value, 10) : null,
+                });
+              }}
+            >
+              <option value="">Any age</option>
+              <option value="30">Not used in 30 days</option>
+              <option value="90">Not used in 90 days</option>
+              <option value="365">Not used in a year</option>
+              <option value="730">Not used in 2 years</option>
+            </HTMLSelect>
+            <Divider />
+            <Switch
+              style={{ margin: "auto 0" }}
+              checked={showExtensions}
+              label="File types"
+              onChange={() => {
+                this.setState({
+                  showExtensions: !showExtensions,
+                  extensions: null,
+                });
+                this.send({ type: "extensions", subscribe: !showExtensions });
+              }}
+            />
+            <Divider />
+            <Switch
+              style={{ margin: "auto 0" }}
+              checked={showOwners}
+              label="Owners"
+              onChange={() => {
+                this.setState({ showOwners: !showOwners, owners: null });
+                this.send({ type: "owners", subscribe: !showOwners });
+              }}
+            />
+            <Divider />
+            <Button
+              style={{ margin: "auto 0" }}
+              icon="sort-numerical-desc"
+              text="Largest files"
+              onClick={() => {
+                this.setState({ showLargestFiles: true });
+                this.send({ type: "largestFiles", count: LARGEST_FILES_COUNT });
+              }}
+            />
+            <Divider />
+            <Button
+              style={{ margin: "auto 0" }}
+              icon="database"
+              text="Volumes"
+              onClick={() => {
+                this.setState({ showVolumes: true, volumes: null });
+                this.send({ type: "volumes" });
+              }}
+            />
+            {!readOnly ? (
+              <>
+                <Divider />
+                <Button
+                  style={{ margin: "auto 0" }}
+                  icon="duplicate"
+                  text="Find duplicates"
+                  onClick={() => {
+                    this.setState({ showDuplicates: true, duplicates: null });
+                    this.send({ type: "findDuplicates" });
+                  }}
+                />
+                <Divider />
+                <Button
+                  style={{ margin: "auto 0" }}
+                  icon="refresh"
+                  text="Rescan"
+                  disabled={currentDirectory == null}
+                  onClick={() => {
+                    if (currentDirectory) {
+                      this.send({
+                        type: "rescan",
+                        path: currentDirectory.path,
+                      });
+                    }
+                  }}
+                />
+              </>
+            ) : null}
           </div>
+
+          <LargestFilesView
+            isOpen={showLargestFiles}
+            entries={largestFiles}
+            onClose={() => {
+              this.setState({ showLargestFiles: false });
+              this.send({ type: "largestFiles", count: 0 });
+            }}
+            onChangeDirectory={(path) => {
+              this.setState({ showLargestFiles: false });
+              this.send({ type: "largestFiles", count: 0 });
+              this.send({ type: "changeDirectory", path });
+            }}
+          />
+
+          <DuplicatesView
+            isOpen={showDuplicates}
+            duplicates={duplicates}
+            onClose={() => {
+              this.setState({ showDuplicates: false });
+            }}
+            onChangeDirectory={(path) => {
+              this.setState({ showDuplicates: false });
+              this.send({ type: "changeDirectory", path });
+            }}
+          />
         </div>
       );
     });
@@ -0,0 +1,79 @@
+import React from "react";
+import { Card, H5, HTMLTable } from "@blueprintjs/core";
+import { bytes, SizeKind } from "./helpers";
+
+const SizeColumnStyle: { textAlign: "right" } = { textAlign: "right" };
+
+class OwnerTable extends React.Component<
+  {
+    title: string;
+    owners: Array<OwnerSize>;
+    sizeKind: SizeKind;
+  },
+  {}
+> {
+  render() {
+    const { title, owners, sizeKind } = this.props;
+
+    const sorted = owners
+      .slice(0)
+      .sort((left, right) => right[sizeKind] - left[sizeKind]);
+
+    return (
+      <HTMLTable condensed style={{ width: "100%" }}>
+        <thead>
+          <tr>
+            <th colSpan={2}>{title}</th>
+          </tr>
+        </thead>
+        <tbody>
+          {sorted.map(({ id, name, fileCount, ...sizes }) => {
+            const size = sizes[sizeKind];
+
+            return (
+              <tr key={id}>
+                <td title={`id ${id}, ${fileCount.toLocaleString()} files`}>
+                  {name != null ? name : `#${id}`}
+                </td>
+                <td
+                  style={SizeColumnStyle}
+                  title={`${size.toLocaleString()} bytes`}
+                >
+                  {bytes(size)}
+                </td>
+              </tr>
+            );
+          })}
+        </tbody>
+      </HTMLTable>
+    );
+  }
+}
+
+interface OwnersViewProps {
+  /** null until the first breakdown arrives */
+  owners: EventMessageOwners | null;
+  sizeKind: SizeKind;
+}
+
+export default class OwnersView extends React.Component<OwnersViewProps, {}> {
+  render() {
+    const { owners, sizeKind } = this.props;
+
+    return (
+      <Card style={{ minWidth: "280px" }}>
+        <H5>Owners</H5>
+        <OwnerTable
+          title="Users"
+          owners={owners != null ? owners.users : []}
+          sizeKind={sizeKind}
+        />
+        <OwnerTable
+          title="Groups"
+          owners={owners != null ? owners.groups : []}
+          sizeKind={sizeKind}
+        />
+      </Card>
+    );
+  }
+}
@@ -0,0 +1,114 @@
+import React from "react";
+import {
+  Button,
+  Card,
+  H5,
+  HTMLTable,
+  Intent,
+  ProgressBar,
+  Spinner,
+} from "@blueprintjs/core";
+import { bytes } from "./helpers";
+
+const SizeColumnStyle: { textAlign: "right" } = { textAlign: "right" };
+const UsageColumnStyle = { width: "160px", verticalAlign: "middle" };
+
+interface VolumesViewProps {
+  /** null until the list arrives */
+  volumes: Array<Volume> | null;
+  onScan: (mountPoint: string) => void;
+  /** back to the current scan, if there is one */
+  onClose?: () => void;
+}
+
+export default class VolumesView extends React.Component<
+  VolumesViewProps,
+  {}
+> {
+  render() {
+    const { volumes, onScan, onClose } = this.props;
+
+    return (
+      <Card>
+        <div style={{ display: "flex" }}>
+          <H5 style={{ flexGrow: 1 }}>Volumes</H5>
+          {onClose ? (
+            <Button minimal icon="cross" title="Close" onClick={onClose} />
+          ) : null}
+        </div>
+        {volumes == null ? (
+          <Spinner />
+        ) : (
+          <HTMLTable condensed striped style={{ width: "100%" }}>
+            <thead>
+              <tr>
+                <th>Mount point</th>
+                <th>Type</th>
+                <th>Source</th>
+                <th style={SizeColumnStyle}>Size</th>
+                <th style={SizeColumnStyle}>Used</th>
+                <th style={SizeColumnStyle}>Free</th>
+                <th />
+                <th />
+              </tr>
+            </thead>
+            <tbody>
+              {volumes.map((volume) => {
+                const { mountPoint, totalSpace, usedSpace } = volume;
+                const { availableSpace } = volume;
+                const used = totalSpace > 0 ? usedSpace / totalSpace : 0;
+
+                return (
+                  <tr key={mountPoint}>
+                    <td>{mountPoint}</td>
+                    <td>{volume.fsType}</td>
+                    <td>{volume.source}</td>
+                    <td
+                      style={SizeColumnStyle}
+                      title={`${totalSpace.toLocaleString()} bytes`}
+                    >
+                      {bytes(totalSpace)}
+                    </td>
+                    <td
+                      style={SizeColumnStyle}
+                      title={`${usedSpace.toLocaleString()} bytes`}
+                    >
+                      {bytes(usedSpace)}
+                    </td>
+                    <td
+                      style={SizeColumnStyle}
+                      title={`${availableSpace.toLocaleString()} bytes`}
+                    >
+                      {bytes(availableSpace)}
+                    </td>
+                    <td
+                      style={UsageColumnStyle}
+                      title={`${Math.round(used * 100)}% used`}
+                    >
+                      <ProgressBar
+                        animate={false}
+                        stripes={false}
+                        intent={used > 0.9 ? Intent.DANGER : Intent.PRIMARY}
+                        value={used}
+                      />
+                    </td>
+                    <td>
+                      <Button
+                        small
+                        icon="search"
+                        text="Scan"
+                        onClick={() => {
+                          onScan(mountPoint);
+                        }}
+                      />
+                    </td>
+                  </tr>
+                );
+              })}
+            </tbody>
+          </HTMLTable>
+        )}
+      </Card>
+    );
+  }
+}
@@ -1,33 +1,157 @@
 type Path = Array<string>;
 
-declare type Entry = EntryFile | EntryDirectory;
+declare type Entry =
+  | EntryFile
+  | EntryDirectory
+  | EntryMountPoint
+  | EntryExcluded
+  | EntryError
+  | EntrySymlink
+  | EntrySpecial;
 
 interface EntryFile {
   type: "file";
   path: Path;
   size: number;
+  allocatedSize: number;
+  /** only when comparing against an older scan */
+  previousSize: number | null;
+  sizeChange: number | null;
+  /** seconds since the unix epoch */
+  modified: number | null;
+  accessed: number | null;
 }
 
 interface EntryDirectory {
   type: "directory";
   path: Path;
   size: number;
+  allocatedSize: number;
   updating: "idle" | "updating" | "finished";
+  hardLinks: number;
+  hardLinksSize: number;
+  previousSize: number | null;
+  sizeChange: number | null;
+  /** recursive */
+  fileCount: number;
+  dirCount: number;
+  /** newest of any file inside */
+  lastModified: number | null;
+  lastAccessed: number | null;
+  /**
+   * bytes last touched less than a day, a week, a month, a year,
+   * and longer ago, counted from the scan
+   */
+  ageHistogram: [number, number, number, number, number];
+  /** why it couldn't be listed */
+  error: string | null;
+  /** recursive, when not 0 the totals are missing something */
+  unreadableCount: number;
+}
+
+/** directory on another filesystem, not scanned */
+interface EntryMountPoint {
+  type: "mountPoint";
+  path: Path;
+}
+
+/** directory matching an exclude pattern, not scanned */
+interface EntryExcluded {
+  type: "excluded";
+  path: Path;
+}
+
+/** file that couldn't be read, not counted anywhere */
+interface EntryError {
+  type: "error";
+  path: Path;
+  message: string;
+}
+
+/** link that wasn't followed, its own size isn't in any total */
+interface EntrySymlink {
+  type: "symlink";
+  path: Path;
+  target: string;
+  size: number;
+}
+
+/** neither file, directory nor symlink, takes no space */
+interface EntrySpecial {
+  type: "special";
+  path: Path;
+  kind: "socket" | "fifo" | "charDevice" | "blockDevice" | "other";
+}
+
+/** total of all files with one extension */
+interface ExtensionSize {
+  /** lowercase, empty for files without one */
+  extension: string;
+  size: number;
+  allocatedSize: number;
+  fileCount: number;
+}
+
+/** total of all files owned by one user or group */
+interface OwnerSize {
+  /** uid or gid */
+  id: number;
+  /** null if the server's user database doesn't have it */
+  name: string | null;
+  size: number;
+  allocatedSize: number;
+  fileCount: number;
+}
+
+/** files with the same contents */
+interface DuplicateGroup {
+  /** of each file */
+  size: number;
+  paths: Array<Path>;
+}
+
+/** mounted filesystem that can be picked to scan */
+interface Volume {
+  mountPoint: string;
+  /** what's mounted, like a device or a network share */
+  source: string;
+  fsType: string;
+  totalSpace: number;
+  /** including what's reserved for root */
+  usedSpace: number;
+  /** to normal users */
+  availableSpace: number;
 }
 
 // Event Messages
 
 declare type EventMessage =
+  | EventMessageSessionInfo
   | EventMessageDirectoryChange
   | EventMessageSizeUpdate
-  | EventMessageDeleting;
+  | EventMessageLargestFiles
+  | EventMessageExtensions
+  | EventMessageOwners
+  | EventMessageDuplicates
+  | EventMessageDeleting
+  | EventMessageVolumes
+  | EventMessageError;
+
+interface EventMessageSessionInfo {
+  type: "sessionInfo";
+  /** empty when several roots are under a virtual top level */
+  rootPath: Path;
+  /** delete and reveal aren't available, e.g. for imported scans */
+  readOnly: boolean;
+}
 
 interface EventMessageDirectoryChange {
   type: "directoryChange";
   currentDirectory: EntryDirectory;
   entries: Array<Entry>;
   breadcrumbEntries: Array<Entry>;
-  availableSpace: number;
+  /** null when browsing an imported scan */
+  availableSpace: number | null;
 }
 
 interface EventMessageSizeUpdate {
@@ -35,18 +159,72 @@ interface EventMessageSizeUpdate {
   entry: EntryDirectory;
 }
 
+/** largest first, resent while scanning */
+interface EventMessageLargestFiles {
+  type: "largestFiles";
+  entries: Array<EntryFile>;
+}
+
+/** breakdown of everything under path, biggest first */
+interface EventMessageExtensions {
+  type: "extensions";
+  path: Path;
+  extensions: Array<ExtensionSize>;
+}
+
+/** who owns everything under path, biggest first */
+interface EventMessageOwners {
+  type: "owners";
+  path: Path;
+  users: Array<OwnerSize>;
+  groups: Array<OwnerSize>;
+}
+
+/** answer to findDuplicates, biggest savings first */
+interface EventMessageDuplicates {
+  type: "duplicates";
+  groups: Array<DuplicateGroup>;
+  reclaimableSize: number;
+}
+
 interface EventMessageDeleting {
   type: "deleting";
   path: Path;
   status: "deleting" | "finished";
 }
 
+/** answer to volumes, also sent first when nothing is scanned yet */
+interface EventMessageVolumes {
+  type: "volumes";
+  volumes: Array<Volume>;
+}
+
+/** something a control message asked for went wrong */
+interface EventMessageError {
+  type: "error";
+  path: Path | null;
+  message: string;
+  kind:
+    | "invalidMessage"
+    | "notFound"
+    | "permissionDenied"
+    | "unsupported"
+    | "other";
+}
+
 // Control Messages
 
 declare type ControlMessage =
   | ControlMessageChangeDirectory
   | ControlMessageDelete
-  | ControlMessageReveal;
+  | ControlMessageReveal
+  | ControlMessageLargestFiles
+  | ControlMessageExtensions
+  | ControlMessageOwners
+  | ControlMessageFindDuplicates
+  | ControlMessageRescan
+  | ControlMessageVolumes
+  | ControlMessageScanVolume;
 
 interface ControlMessageChangeDirectory {
   type: "changeDirectory";
@@ -62,3 +240,43 @@ interface ControlMessageReveal {
   type: "reveal";
   path: Path;
 }
+
+/** subscribe to the largest files, 0 to stop */
+interface ControlMessageLargestFiles {
+  type: "largestFiles";
+  count: number;
+}
+
+/** get extension breakdowns of the current directory */
+interface ControlMessageExtensions {
+  type: "extensions";
+  subscribe: boolean;
+}
+
+/** get owner breakdowns of the current directory */
+interface ControlMessageOwners {
+  type: "owners";
+  subscribe: boolean;
+}
+
+/** hash same-size files once scanning is done */
+interface ControlMessageFindDuplicates {
+  type: "findDuplicates";
+}
+
+/** walk this again to correct its totals after changes made elsewhere */
+interface ControlMessageRescan {
+  type: "rescan";
+  path: Path;
+}
+
+/** list the mounted filesystems */
+interface ControlMessageVolumes {
+  type: "volumes";
+}
+
+/** scan a filesystem from volumes, a new sessionInfo starts it over */
+interface ControlMessageScanVolume {
+  type: "scanVolume";
+  mountPoint: string;
+}
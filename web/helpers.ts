@@ -6,6 +6,109 @@ export function bytes(b: number): string {
   return filesize(b, { standard: "iec" });
 }
 
+/** "size" is the apparent size, "allocatedSize" is the space used on disk */
+export type SizeKind = "size" | "allocatedSize";
+
+export function entrySize(entry: Entry, sizeKind: SizeKind): number {
+  if (
+    entry.type === "mountPoint" ||
+    entry.type === "excluded" ||
+    entry.type === "error" ||
+    entry.type === "symlink" ||
+    entry.type === "special"
+  ) {
+    return 0;
+  }
+
+  return entry[sizeKind];
+}
+
+export type SortBy = "size" | "change" | "files" | "age";
+
+/** files and directories inside, a file counts as itself */
+export function entryCount(entry: Entry): number {
+  if (entry.type === "directory") {
+    return entry.fileCount + entry.dirCount;
+  } else if (entry.type === "file") {
+    return 1;
+  } else {
+    return 0;
+  }
+}
+
+export function bytesChange(b: number): string {
+  return b < 0 ? `-${bytes(-b)}` : `+${bytes(b)}`;
+}
+
+export function entrySizeChange(entry: Entry): number | null {
+  if (
+    entry.type === "mountPoint" ||
+    entry.type === "excluded" ||
+    entry.type === "error" ||
+    entry.type === "symlink" ||
+    entry.type === "special"
+  ) {
+    return null;
+  }
+
+  return entry.sizeChange;
+}
+
+function newest(a: number | null, b: number | null): number | null {
+  if (a == null) return b;
+  if (b == null) return a;
+  return Math.max(a, b);
+}
+
+/** newer of modified and accessed, in seconds since the unix epoch */
+export function entryLastTouched(entry: Entry): number | null {
+  if (entry.type === "file") {
+    return newest(entry.modified, entry.accessed);
+  } else if (entry.type === "directory") {
+    return newest(entry.lastModified, entry.lastAccessed);
+  } else {
+    return null;
+  }
+}
+
+/** labels for EntrySpecial.kind */
+export const SPECIAL_KINDS = {
+  socket: "socket",
+  fifo: "named pipe",
+  charDevice: "character device",
+  blockDevice: "block device",
+  other: "special file",
+};
+
+const DAY = 24 * 60 * 60;
+
+/** labels for EntryDirectory.ageHistogram */
+export const AGE_BUCKETS = [
+  "< 1 day",
+  "< 1 week",
+  "< 1 month",
+  "< 1 year",
+  "older",
+];
+
+export function daysAgo(days: number): number {
+  return Date.now() / 1000 - days * DAY;
+}
+
+export function age(seconds: number): string {
+  const days = Math.floor((Date.now() / 1000 - seconds) / DAY);
+
+  if (days < 1) {
+    return "today";
+  } else if (days < 60) {
+    return `${days} days ago`;
+  } else if (days < 730) {
+    return `${Math.floor(days / 30)} months ago`;
+  } else {
+    return `${Math.floor(days / 365)} years ago`;
+  }
+}
+
 export function time<T>(name: string, cb: () => T): T {
   const before = new Date();
   const ret = cb();